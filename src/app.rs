@@ -1,48 +1,404 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+
 use ratatui::widgets::ListState; 
+use serde::{Deserialize, Deserializer, Serialize};
 
-use crate::commands::load_commands_from_file; 
+use crate::commands::{next_command_id, CommandStore};
+use crate::config::Config;
+use crate::danger;
+use crate::execute;
+use crate::fuzzy::fuzzy_match;
+use crate::history::{self, HistoryEntry, Invocation};
+use crate::query::{parse_query, score_command};
+use crate::pty::PtySession;
+use crate::runner::{OutputLine, RunningCommand, SourceLines};
+use crate::shell_history::HistoryCandidate;
+use crate::template::{self, Placeholder};
 
 
-#[derive(Clone)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct BashCmd {
+    #[serde(default)]
+    pub id: u64,
     pub name: String,
+    #[serde(default)]
     pub desc: String,
     pub command: String,
-    pub index: u8,
-    pub category: u8,
+    #[serde(default, deserialize_with = "deserialize_category", skip_serializing_if = "String::is_empty")]
+    pub category: String,
+    #[serde(default)]
     pub favourite: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    // Directory the command runs in; `~` and $VARIABLES are expanded when it runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workdir: Option<String>,
+    // Set on top of the inherited environment, values expanded like `workdir`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    // Ask for typed confirmation before every run, even if no danger rule matches.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub always_confirm: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CategoryField {
+    Name(String),
+    // Store version 1 wrote a placeholder number for every command.
+    Legacy(#[allow(dead_code)] i64),
+}
+
+fn deserialize_category<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(match CategoryField::deserialize(deserializer)? {
+        CategoryField::Name(name) => name.trim().to_string(),
+        CategoryField::Legacy(_) => String::new(),
+    })
+}
+
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum CategoryFilter {
+    All,
+    Named(String),
+    Uncategorized,
+}
+
+impl CategoryFilter {
+    pub fn label(&self) -> &str {
+        match self {
+            CategoryFilter::All => "All",
+            CategoryFilter::Named(name) => name,
+            CategoryFilter::Uncategorized => "Uncategorized",
+        }
+    }
+
+    pub fn matches(&self, command: &BashCmd) -> bool {
+        match self {
+            CategoryFilter::All => true,
+            CategoryFilter::Named(name) => &command.category == name,
+            CategoryFilter::Uncategorized => command.category.is_empty(),
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortMode {
+    #[default]
+    Frecency,
+    Alphabetical,
+    Manual,
+}
+
+impl SortMode {
+    pub fn next(self) -> SortMode {
+        match self {
+            SortMode::Frecency => SortMode::Alphabetical,
+            SortMode::Alphabetical => SortMode::Manual,
+            SortMode::Manual => SortMode::Frecency,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortMode::Frecency => "frecency",
+            SortMode::Alphabetical => "alphabetical",
+            SortMode::Manual => "manual",
+        }
+    }
 }
 
 
+pub struct ParamField {
+    pub placeholder: Placeholder,
+    pub value: String,
+    pub options: Vec<String>,
+    pub option_state: ListState,
+    pub source_error: Option<String>,
+    pub loading: Option<SourceLines>,
+}
 
+impl ParamField {
+    fn new(placeholder: Placeholder, command: &BashCmd, config: &Config) -> ParamField {
+        let mut field = ParamField {
+            value: placeholder.default.clone().unwrap_or_default(),
+            placeholder,
+            options: Vec::new(),
+            option_state: ListState::default(),
+            source_error: None,
+            loading: None,
+        };
+        if let Some(source) = &field.placeholder.source {
+            // The source runs with the same shell settings as the command it belongs to, but
+            // nothing the danger rules flag runs without the confirmation a command would get.
+            let source_command = BashCmd { command: source.clone(), always_confirm: false, ..command.clone() };
+            let warnings = danger::warnings(&source_command, &config.danger);
+            if !warnings.is_empty() {
+                field.source_error = Some(format!("not run, {}", warnings.join(", ")));
+            } else {
+                match SourceLines::start(&source_command, config) {
+                    Ok(loading) => field.loading = Some(loading),
+                    Err(err) => field.source_error = Some(err.to_string()),
+                }
+            }
+        }
+        field.update_option_selection();
+        field
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.loading.is_some()
+    }
+
+    fn poll_source(&mut self) {
+        let Some(result) = self.loading.as_mut().and_then(SourceLines::poll) else {
+            return;
+        };
+        self.loading = None;
+        match result {
+            Ok(options) => self.options = options,
+            Err(err) => self.source_error = Some(err.to_string()),
+        }
+        self.update_option_selection();
+    }
+
+    pub fn has_source(&self) -> bool {
+        self.placeholder.source.is_some()
+    }
+
+    // The typed value filters and ranks the pick list, just like the filter box does for commands.
+    pub fn filtered_options(&self) -> Vec<&String> {
+        let mut scored: Vec<(i64, &String)> = self.options
+            .iter()
+            .filter_map(|option| fuzzy_match(&self.value, option).map(|(score, _)| (score, option)))
+            .collect();
+        scored.sort_by_key(|&(score, _)| Reverse(score));
+        scored.into_iter().map(|(_, option)| option).collect()
+    }
+
+    pub fn resolved_value(&self) -> String {
+        let selected_option = self.option_state.selected()
+            .and_then(|index| self.filtered_options().get(index).map(|option| option.to_string()));
+        selected_option.unwrap_or_else(|| self.value.clone())
+    }
+
+    // Typed text is submitted as typed; an option is only taken once Up/Down highlights it. With
+    // nothing typed yet the first option is highlighted, as there is no value to lose.
+    fn update_option_selection(&mut self) {
+        let preselect = self.value.is_empty() && !self.filtered_options().is_empty();
+        self.option_state.select(if preselect { Some(0) } else { None });
+    }
+
+    fn next_option(&mut self) {
+        let len = self.filtered_options().len();
+        if len > 0 {
+            let i = self.option_state.selected().map_or(0, |i| (i + 1) % len);
+            self.option_state.select(Some(i));
+        }
+    }
+
+    fn previous_option(&mut self) {
+        let len = self.filtered_options().len();
+        if len > 0 {
+            let i = self.option_state.selected().map_or(0, |i| (i + len - 1) % len);
+            self.option_state.select(Some(i));
+        }
+    }
+}
+
+
+// How a chosen command is run from the TUI: by replacing the process (Enter), as a child with
+// its output in the output pane (Ctrl+R), in the embedded terminal (Ctrl+P), or not at all but
+// shown in the preview popup (Alt+D).
+#[derive(Clone, Copy, PartialEq)]
+pub enum RunMode {
+    Exec,
+    Inside,
+    Terminal,
+    Preview,
+}
+
+
+// State of the `import-history` multi-select list.
+pub struct ImportPicker {
+    pub candidates: Vec<HistoryCandidate>,
+    pub chosen: Vec<bool>,
+    pub state: ListState,
+}
+
+impl ImportPicker {
+    pub fn new(candidates: Vec<HistoryCandidate>) -> ImportPicker {
+        let mut state = ListState::default();
+        if !candidates.is_empty() {
+            state.select(Some(0));
+        }
+        ImportPicker { chosen: vec![false; candidates.len()], candidates, state }
+    }
+
+    pub fn chosen_count(&self) -> usize {
+        self.chosen.iter().filter(|&&chosen| chosen).count()
+    }
+
+    pub fn move_by(&mut self, offset: isize) {
+        if self.candidates.is_empty() {
+            return;
+        }
+        let last = self.candidates.len() - 1;
+        let current = self.state.selected().unwrap_or(0);
+        self.state.select(Some(current.saturating_add_signed(offset).min(last)));
+    }
+
+    pub fn toggle_selected(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.chosen[i] = !self.chosen[i];
+        }
+    }
+
+    // Selects everything unless everything is already selected, then clears the selection.
+    pub fn toggle_all(&mut self) {
+        let all_chosen = self.chosen.iter().all(|&chosen| chosen);
+        self.chosen.iter_mut().for_each(|chosen| *chosen = !all_chosen);
+    }
+
+    pub fn chosen_commands(self) -> Vec<String> {
+        self.candidates
+            .into_iter()
+            .zip(self.chosen)
+            .filter(|(_, chosen)| *chosen)
+            .map(|(candidate, _)| candidate.command)
+            .collect()
+    }
+}
+
+
+// Tags are typed comma- or space-separated, with or without a leading `#`.
+pub fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in input.split([',', ' ']) {
+        let tag = tag.trim().trim_start_matches('#');
+        if !tag.is_empty() && !tags.iter().any(|existing| existing == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+// Environment overrides are typed as space-separated NAME=value pairs, quoted like in a shell
+// when a value has spaces: `GIT_SSH_COMMAND='ssh -i key'`.
+pub fn parse_env(input: &str) -> Result<BTreeMap<String, String>, String> {
+    execute::split_shell_words(input)?.iter().map(|word| parse_env_assignment(word)).collect()
+}
+
+pub fn is_env_name(name: &str) -> bool {
+    name.chars().next().is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && name.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+pub fn parse_env_assignment(assignment: &str) -> Result<(String, String), String> {
+    match assignment.split_once('=') {
+        Some((name, value)) if is_env_name(name) => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("invalid environment variable '{}', expected NAME=value", assignment)),
+    }
+}
+
+pub fn format_env(env: &BTreeMap<String, String>) -> String {
+    env.iter().map(|(name, value)| format!("{}={}", name, execute::shell_quote(value))).collect::<Vec<_>>().join(" ")
+}
 
 
 pub struct App {
     pub items: Vec<BashCmd>,
     pub filtered_items: Vec<BashCmd>,
+    pub match_positions: HashMap<u64, Vec<usize>>,
+    pub sort_mode: SortMode,
+    pub frecency: HashMap<u64, f64>,
+    pub favourites_only: bool,
+    pub categories: Vec<String>,
+    pub category_state: ListState,
+    pub show_category_popup: bool,
+    pub category_popup_state: ListState,
+    pub category_input: Option<String>,
+    pub renaming_category: Option<String>,
+    pub show_tags_popup: bool,
+    pub tags_popup_state: ListState,
     pub state: ListState,
     pub filter_text: String,
     pub show_popup: bool,
     pub popup_input_name: String,
     pub popup_input_desc: String,
     pub popup_input_command: String,
+    pub popup_input_shell: String,
+    pub popup_input_category: String,
+    pub popup_input_tags: String,
+    pub popup_input_workdir: String,
+    pub popup_input_env: String,
+    // Why the add/edit popup can't be saved yet.
+    pub popup_error: Option<String>,
     pub popup_input_focused: u8, 
     pub is_editing: bool,               
-    pub editing_command_index: Option<usize>, 
-    pub selected_command_to_execute: Option<String>, 
+    pub editing_command_id: Option<u64>, 
+    pub selected_command_to_execute: Option<BashCmd>, 
+    pub deleting_command_id: Option<u64>,
+    pub show_params_popup: bool,
+    pub params: Vec<ParamField>,
+    pub param_focused: usize,
+    pub params_command: Option<BashCmd>,
+    // What Enter in the params form does with the expanded command.
+    pub params_run_mode: RunMode,
+    // Placeholder values of the command last expanded by the params form, recorded with its run.
+    pub chosen_params: BTreeMap<String, String>,
+    // Last command run inside the TUI, shown in the output pane until closed.
+    pub running: Option<RunningCommand>,
+    pub running_entry: Option<HistoryEntry>,
+    pub run_error: Option<String>,
+    // Lines scrolled up from the bottom of the output pane; 0 follows new output.
+    pub output_scroll: usize,
+    // Command running in the embedded terminal; it keeps running while detached.
+    pub terminal_session: Option<PtySession>,
+    pub terminal_entry: Option<HistoryEntry>,
+    pub terminal_attached: bool,
+    pub show_history_popup: bool,
+    // Numbered like `bash-commander history`, newest first.
+    pub history_entries: Vec<(usize, HistoryEntry)>,
+    pub history_popup_state: ListState,
+    // The run being replayed; its directory and profile apply to the replay only.
+    pub replay_entry: Option<HistoryEntry>,
+    // Every key binding of the list view (`?`); the help bar only has room for a few.
+    pub show_help_popup: bool,
+    // Profile switcher: "no profile" first, then the profiles of config.toml.
+    pub show_profile_popup: bool,
+    pub profile_popup_state: ListState,
+    // Dry run of an expanded command: what would be executed, and why it would ask first.
+    pub preview_command: Option<BashCmd>,
+    pub preview_lines: Vec<String>,
+    pub preview_warnings: Vec<String>,
+    // A command flagged by the danger rules, waiting for its name to be typed.
+    pub confirm_command: Option<BashCmd>,
+    pub confirm_run_mode: RunMode,
+    pub confirm_warnings: Vec<String>,
+    pub confirm_input: String,
+    pub config: Config,
 }
 
 
 
 impl App {
-    pub fn new() -> App {
+    pub fn new(config: Config, store: CommandStore) -> App {
          let initial_items = vec![
-            BashCmd { name: "ls".to_string(), desc: "List files".to_string(), command: "ls -l".to_string(), index: 1, category: 1, favourite: false },
+            BashCmd { name: "ls".to_string(), desc: "List files".to_string(), command: "ls -l".to_string(), id: 1, favourite: false, ..Default::default() },
          ];
 
-        let loaded_commands = load_commands_from_file().unwrap_or_else(|_| Vec::new()); 
-        let items_to_use = if !loaded_commands.is_empty() { 
-            loaded_commands
+        let items_to_use = if !store.commands.is_empty() { 
+            store.commands
         } else { 
             initial_items
         };
@@ -50,31 +406,104 @@ impl App {
         App {
             items: items_to_use.clone(),
             filtered_items: items_to_use.clone(),
+            match_positions: HashMap::new(),
+            sort_mode: config.sort_mode,
+            frecency: HashMap::new(),
+            favourites_only: false,
+            categories: store.categories,
+            category_state: ListState::default().with_selected(Some(0)),
+            show_category_popup: false,
+            category_popup_state: ListState::default(),
+            category_input: None,
+            renaming_category: None,
+            show_tags_popup: false,
+            tags_popup_state: ListState::default(),
             state: ListState::default(),
             filter_text: String::new(),
             show_popup: false, 
             popup_input_name: String::new(),    
             popup_input_desc: String::new(),    
             popup_input_command: String::new(), 
+            popup_input_shell: String::new(),
+            popup_input_category: String::new(),
+            popup_input_tags: String::new(),
+            popup_input_workdir: String::new(),
+            popup_input_env: String::new(),
+            popup_error: None,
             popup_input_focused: 0, 
             is_editing: false,                
-            editing_command_index: None,         
+            editing_command_id: None,         
             selected_command_to_execute: None, 
-            
+            deleting_command_id: None,
+            show_params_popup: false,
+            params: Vec::new(),
+            param_focused: 0,
+            params_command: None,
+            params_run_mode: RunMode::Exec,
+            chosen_params: BTreeMap::new(),
+            running: None,
+            running_entry: None,
+            run_error: None,
+            output_scroll: 0,
+            terminal_session: None,
+            terminal_entry: None,
+            terminal_attached: false,
+            show_history_popup: false,
+            history_entries: Vec::new(),
+            history_popup_state: ListState::default(),
+            replay_entry: None,
+            show_help_popup: false,
+            show_profile_popup: false,
+            profile_popup_state: ListState::default(),
+            preview_command: None,
+            preview_lines: Vec::new(),
+            preview_warnings: Vec::new(),
+            confirm_command: None,
+            confirm_run_mode: RunMode::Exec,
+            confirm_warnings: Vec::new(),
+            confirm_input: String::new(),
+            config,
         }
     }
 
     
+    fn ordered_items(&self) -> Vec<&BashCmd> {
+        let mut ordered: Vec<&BashCmd> = self.items.iter().collect();
+        match self.sort_mode {
+            SortMode::Frecency => ordered.sort_by(|a, b| {
+                let score = |command: &BashCmd| self.frecency.get(&command.id).copied().unwrap_or(0.0);
+                score(b).total_cmp(&score(a))
+            }),
+            SortMode::Alphabetical => ordered.sort_by_key(|command| command.name.to_lowercase()),
+            SortMode::Manual => {}
+        }
+        if self.favourites_only {
+            ordered.retain(|command| command.favourite);
+        }
+        let category = self.active_category();
+        ordered.retain(|command| category.matches(command));
+        ordered
+    }
+
     fn update_filtered_items(&mut self) {
-        self.filtered_items = self.items
+        let terms = parse_query(&self.filter_text);
+        let mut scored: Vec<(i64, BashCmd, Vec<usize>)> = self.ordered_items()
+            .into_iter()
+            .filter_map(|item| score_command(item, &terms).map(|(score, positions)| (score, item.clone(), positions)))
+            .collect();
+        // Favourites stay pinned on top; otherwise the stable sort keeps the order of the sort
+        // mode for equal scores (and for an empty filter).
+        scored.sort_by_key(|(score, item, _)| (Reverse(item.favourite), Reverse(*score)));
+
+        self.match_positions = scored
             .iter()
-            .filter(|item| item.name.to_lowercase().contains(&self.filter_text.to_lowercase()))
-            .cloned()
+            .map(|(_, item, positions)| (item.id, positions.clone()))
             .collect();
+        self.filtered_items = scored.into_iter().map(|(_, item, _)| item).collect();
         if self.state.selected().is_some() && self.state.selected().unwrap() >= self.filtered_items.len() {
             self.state.select(if self.filtered_items.is_empty() { None } else { Some(0) });
         } else if self.filtered_items.is_empty() {
-            self.state.select(None);
+            self.unselect();
         }
     }
 
@@ -82,11 +511,266 @@ impl App {
     pub fn add_char_to_filter(&mut self, ch: char) {
         self.filter_text.push(ch);
         self.update_filtered_items();
+        self.select_best_match();
     }
 
     pub fn remove_char_from_filter(&mut self) {
         self.filter_text.pop();
         self.update_filtered_items();
+        self.select_best_match();
+    }
+
+    pub fn set_frecency(&mut self, frecency: HashMap<u64, f64>) {
+        self.frecency = frecency;
+        self.update_filtered_items();
+    }
+
+    pub fn cycle_sort_mode(&mut self) {
+        let selected_id = self.selected_command_id();
+        self.sort_mode = self.sort_mode.next();
+        self.update_filtered_items();
+        if let Some(id) = selected_id {
+            self.select_by_id(id);
+        }
+    }
+
+    pub fn toggle_favourite(&mut self, id: u64) {
+        if let Some(command) = self.items.iter_mut().find(|item| item.id == id) {
+            command.favourite = !command.favourite;
+        }
+        self.update_filtered_items();
+        self.select_by_id(id);
+    }
+
+    pub fn toggle_always_confirm(&mut self, id: u64) {
+        if let Some(command) = self.items.iter_mut().find(|item| item.id == id) {
+            command.always_confirm = !command.always_confirm;
+        }
+        self.update_filtered_items();
+        self.select_by_id(id);
+    }
+
+    pub fn toggle_favourites_only(&mut self) {
+        let selected_id = self.selected_command_id();
+        self.favourites_only = !self.favourites_only;
+        self.update_filtered_items();
+        if let Some(id) = selected_id {
+            self.select_by_id(id);
+        }
+    }
+
+    // Sidebar entries: "All", every named category, then "Uncategorized".
+    pub fn category_entries(&self) -> Vec<CategoryFilter> {
+        let mut entries = vec![CategoryFilter::All];
+        entries.extend(self.categories.iter().cloned().map(CategoryFilter::Named));
+        entries.push(CategoryFilter::Uncategorized);
+        entries
+    }
+
+    pub fn active_category(&self) -> CategoryFilter {
+        let entries = self.category_entries();
+        self.category_state.selected()
+            .and_then(|i| entries.get(i).cloned())
+            .unwrap_or(CategoryFilter::All)
+    }
+
+    fn select_category_entry(&mut self, i: usize) {
+        let selected_id = self.selected_command_id();
+        self.category_state.select(Some(i));
+        self.update_filtered_items();
+        match selected_id {
+            Some(id) => self.select_by_id(id),
+            None => self.unselect(),
+        }
+        if self.state.selected().is_none() && !self.filtered_items.is_empty() {
+            self.state.select(Some(0));
+        }
+    }
+
+    pub fn next_category(&mut self) {
+        let len = self.category_entries().len();
+        let i = self.category_state.selected().map_or(0, |i| (i + 1) % len);
+        self.select_category_entry(i);
+    }
+
+    pub fn previous_category(&mut self) {
+        let len = self.category_entries().len();
+        let i = self.category_state.selected().map_or(0, |i| (i + len - 1) % len);
+        self.select_category_entry(i);
+    }
+
+    pub fn add_category(&mut self, name: &str) -> bool {
+        let name = name.trim();
+        if name.is_empty() || self.categories.iter().any(|category| category == name) {
+            return false;
+        }
+        self.categories.push(name.to_string());
+        true
+    }
+
+    pub fn rename_category(&mut self, old_name: &str, new_name: &str) -> bool {
+        let new_name = new_name.trim();
+        if new_name.is_empty() || (new_name != old_name && self.categories.iter().any(|category| category == new_name)) {
+            return false;
+        }
+        let Some(category) = self.categories.iter_mut().find(|category| *category == old_name) else {
+            return false;
+        };
+        *category = new_name.to_string();
+        for item in self.items.iter_mut().filter(|item| item.category == old_name) {
+            item.category = new_name.to_string();
+        }
+        self.update_filtered_items();
+        true
+    }
+
+    // Commands of a deleted category are kept and become uncategorized.
+    pub fn delete_category(&mut self, name: &str) {
+        self.categories.retain(|category| category != name);
+        for item in self.items.iter_mut().filter(|item| item.category == name) {
+            item.category.clear();
+        }
+        self.category_state.select(Some(0));
+        self.update_filtered_items();
+    }
+
+    pub fn show_category_manager(&mut self) {
+        self.show_category_popup = true;
+        self.category_popup_state.select(if self.categories.is_empty() { None } else { Some(0) });
+    }
+
+    pub fn hide_category_manager(&mut self) {
+        self.show_category_popup = false;
+        self.category_input = None;
+        self.renaming_category = None;
+    }
+
+    fn managed_category(&self) -> Option<String> {
+        self.category_popup_state.selected().and_then(|i| self.categories.get(i).cloned())
+    }
+
+    pub fn next_managed_category(&mut self) {
+        if !self.categories.is_empty() {
+            let i = self.category_popup_state.selected().map_or(0, |i| (i + 1) % self.categories.len());
+            self.category_popup_state.select(Some(i));
+        }
+    }
+
+    pub fn previous_managed_category(&mut self) {
+        if !self.categories.is_empty() {
+            let len = self.categories.len();
+            let i = self.category_popup_state.selected().map_or(0, |i| (i + len - 1) % len);
+            self.category_popup_state.select(Some(i));
+        }
+    }
+
+    pub fn start_new_category(&mut self) {
+        self.renaming_category = None;
+        self.category_input = Some(String::new());
+    }
+
+    pub fn start_rename_category(&mut self) {
+        if let Some(name) = self.managed_category() {
+            self.category_input = Some(name.clone());
+            self.renaming_category = Some(name);
+        }
+    }
+
+    pub fn delete_managed_category(&mut self) {
+        if let Some(name) = self.managed_category() {
+            self.delete_category(&name);
+            let len = self.categories.len();
+            self.category_popup_state.select(if len == 0 { None } else { Some(len - 1) });
+        }
+    }
+
+    pub fn add_char_to_category_input(&mut self, ch: char) {
+        if let Some(input) = self.category_input.as_mut() {
+            input.push(ch);
+        }
+    }
+
+    pub fn remove_char_from_category_input(&mut self) {
+        if let Some(input) = self.category_input.as_mut() {
+            input.pop();
+        }
+    }
+
+    pub fn cancel_category_input(&mut self) {
+        self.category_input = None;
+        self.renaming_category = None;
+    }
+
+    pub fn submit_category_input(&mut self) {
+        let Some(input) = self.category_input.take() else {
+            return;
+        };
+        let saved = match self.renaming_category.take() {
+            Some(old_name) => self.rename_category(&old_name, &input),
+            None => self.add_category(&input),
+        };
+        if saved {
+            let position = self.categories.iter().position(|category| category == input.trim());
+            self.category_popup_state.select(position);
+        }
+    }
+
+    // Every tag in use with the number of commands carrying it, most used first.
+    pub fn tag_counts(&self) -> Vec<(String, usize)> {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for tag in self.items.iter().flat_map(|item| item.tags.iter()) {
+            *counts.entry(tag.clone()).or_insert(0) += 1;
+        }
+        let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+        counts.sort_by_key(|(_, count)| Reverse(*count));
+        counts
+    }
+
+    pub fn show_tags_browser(&mut self) {
+        self.show_tags_popup = true;
+        self.tags_popup_state.select(if self.tag_counts().is_empty() { None } else { Some(0) });
+    }
+
+    pub fn hide_tags_browser(&mut self) {
+        self.show_tags_popup = false;
+    }
+
+    pub fn next_tag(&mut self) {
+        let len = self.tag_counts().len();
+        if len > 0 {
+            let i = self.tags_popup_state.selected().map_or(0, |i| (i + 1) % len);
+            self.tags_popup_state.select(Some(i));
+        }
+    }
+
+    pub fn previous_tag(&mut self) {
+        let len = self.tag_counts().len();
+        if len > 0 {
+            let i = self.tags_popup_state.selected().map_or(0, |i| (i + len - 1) % len);
+            self.tags_popup_state.select(Some(i));
+        }
+    }
+
+    // Adds `#tag` for the highlighted tag to the filter box.
+    pub fn filter_by_selected_tag(&mut self) {
+        let tag = self.tags_popup_state.selected()
+            .and_then(|i| self.tag_counts().get(i).map(|(tag, _)| tag.clone()));
+        if let Some(tag) = tag {
+            if !self.filter_text.is_empty() && !self.filter_text.ends_with(' ') {
+                self.filter_text.push(' ');
+            }
+            self.filter_text.push('#');
+            self.filter_text.push_str(&tag);
+            self.update_filtered_items();
+            self.select_best_match();
+        }
+        self.hide_tags_browser();
+    }
+
+    fn select_best_match(&mut self) {
+        if !self.filter_text.is_empty() && !self.filtered_items.is_empty() {
+            self.state.select(Some(0));
+        }
     }
 
     
@@ -128,12 +812,103 @@ impl App {
         self.state.select(None);
     }
 
+    pub fn selected_command(&self) -> Option<&BashCmd> {
+        self.state.selected().and_then(|i| self.filtered_items.get(i))
+    }
+
+    // Positions in `filtered_items` shift with the filter, so operations address commands by ID.
+    pub fn selected_command_id(&self) -> Option<u64> {
+        self.selected_command().map(|command| command.id)
+    }
+
+    pub fn command_by_id(&self, id: u64) -> Option<&BashCmd> {
+        self.items.iter().find(|item| item.id == id)
+    }
+
+    fn position_of(&self, id: u64) -> Option<usize> {
+        self.items.iter().position(|item| item.id == id)
+    }
+
+    fn select_by_id(&mut self, id: u64) {
+        if let Some(i) = self.filtered_items.iter().position(|item| item.id == id) {
+            self.state.select(Some(i));
+        }
+    }
+
+    pub fn request_delete(&mut self) {
+        self.deleting_command_id = self.selected_command_id();
+    }
+
+    pub fn cancel_delete(&mut self) {
+        self.deleting_command_id = None;
+    }
+
+    pub fn confirm_delete(&mut self) {
+        let Some(id) = self.deleting_command_id.take() else {
+            return;
+        };
+        if let Some(position) = self.position_of(id) {
+            self.items.remove(position);
+            self.update_filtered_items();
+        }
+    }
+
+    pub fn duplicate_command(&mut self, id: u64) {
+        let Some(position) = self.position_of(id) else {
+            return;
+        };
+        let original = self.items[position].clone();
+        let copy = BashCmd {
+            id: next_command_id(&self.items),
+            name: format!("{} (copy)", original.name),
+            ..original
+        };
+        let copy_id = copy.id;
+        self.items.insert(position + 1, copy);
+        self.update_filtered_items();
+        self.select_by_id(copy_id);
+    }
+
+    // Moves the selected command past its visible neighbour, so reordering also works while filtered.
+    // Reordering only makes sense on the manual order, so the list switches to it first.
+    pub fn move_selected(&mut self, up: bool) {
+        if self.sort_mode != SortMode::Manual {
+            let selected_id = self.selected_command_id();
+            self.sort_mode = SortMode::Manual;
+            self.update_filtered_items();
+            if let Some(id) = selected_id {
+                self.select_by_id(id);
+            }
+        }
+        let Some(selected) = self.state.selected() else {
+            return;
+        };
+        let neighbour = if up { selected.checked_sub(1) } else { Some(selected + 1) };
+        let (Some(current), Some(other)) = (
+            self.filtered_items.get(selected).map(|item| item.id),
+            neighbour.and_then(|i| self.filtered_items.get(i)).map(|item| item.id),
+        ) else {
+            return;
+        };
+        let (Some(from), Some(to)) = (self.position_of(current), self.position_of(other)) else {
+            return;
+        };
+
+        let command = self.items.remove(from);
+        self.items.insert(to, command);
+        self.update_filtered_items();
+        self.select_by_id(current);
+    }
+
     
     pub fn show_add_popup(&mut self) { 
         self.show_popup = true;
         self.popup_input_focused = 0; 
         self.is_editing = false; 
-        self.editing_command_index = None; 
+        self.editing_command_id = None; 
+        if let CategoryFilter::Named(name) = self.active_category() {
+            self.popup_input_category = name;
+        }
     }
 
     
@@ -142,17 +917,40 @@ impl App {
         self.popup_input_name.clear();
         self.popup_input_desc.clear();
         self.popup_input_command.clear();
+        self.popup_input_shell.clear();
+        self.popup_input_category.clear();
+        self.popup_input_tags.clear();
+        self.popup_input_workdir.clear();
+        self.popup_input_env.clear();
+        self.popup_error = None;
         self.popup_input_focused = 0; 
         self.exit_edit_mode(); 
     }
     
     pub fn save_command(&mut self) {
+        let env = match parse_env(&self.popup_input_env) {
+            Ok(env) => env,
+            Err(err) => {
+                self.popup_error = Some(err);
+                return;
+            }
+        };
+        let category = self.popup_input_category.trim().to_string();
+        self.add_category(&category);
+        let tags = parse_tags(&self.popup_input_tags);
+        let workdir = Some(self.popup_input_workdir.trim().to_string()).filter(|workdir| !workdir.is_empty());
         if self.is_editing { 
-            if let Some(index) = self.editing_command_index {
-                if let Some(command_to_edit) = self.items.get_mut(index) { 
+            if let Some(id) = self.editing_command_id {
+                let shell = self.popup_input_shell_option();
+                if let Some(command_to_edit) = self.items.iter_mut().find(|item| item.id == id) { 
                     command_to_edit.name = self.popup_input_name.clone();
                     command_to_edit.desc = self.popup_input_desc.clone();
                     command_to_edit.command = self.popup_input_command.clone();
+                    command_to_edit.shell = shell;
+                    command_to_edit.category = category;
+                    command_to_edit.tags = tags;
+                    command_to_edit.workdir = workdir;
+                    command_to_edit.env = env;
                 }
             }
         } else { 
@@ -160,9 +958,14 @@ impl App {
                 name: self.popup_input_name.clone(),
                 desc: self.popup_input_desc.clone(),
                 command: self.popup_input_command.clone(),
-                index: (self.items.len() + 1) as u8,
-                category: 1,
+                id: next_command_id(&self.items),
+                category,
+                tags,
                 favourite: false,
+                shell: self.popup_input_shell_option(),
+                workdir,
+                env,
+                ..Default::default()
             };
             self.items.push(new_command);
         }
@@ -171,22 +974,27 @@ impl App {
     }
 
     
-    pub fn enter_edit_mode(&mut self, index: usize) {
+    pub fn enter_edit_mode(&mut self, id: u64) {
         self.is_editing = true;
-        self.editing_command_index = Some(index);
+        self.editing_command_id = Some(id);
 
         
-        if let Some(command_to_edit) = self.filtered_items.get(index) {
+        if let Some(command_to_edit) = self.command_by_id(id).cloned() {
             self.popup_input_name = command_to_edit.name.clone();
             self.popup_input_desc = command_to_edit.desc.clone();
             self.popup_input_command = command_to_edit.command.clone();
+            self.popup_input_shell = command_to_edit.shell.clone().unwrap_or_default();
+            self.popup_input_category = command_to_edit.category.clone();
+            self.popup_input_tags = command_to_edit.tags.join(", ");
+            self.popup_input_workdir = command_to_edit.workdir.clone().unwrap_or_default();
+            self.popup_input_env = format_env(&command_to_edit.env);
         }
         self.show_popup = true; 
     }
 
     pub fn exit_edit_mode(&mut self) {
         self.is_editing = false;
-        self.editing_command_index = None;
+        self.editing_command_id = None;
     }
     
     pub fn add_char_to_popup_input_name(&mut self, ch: char) {
@@ -208,7 +1016,614 @@ impl App {
     pub fn remove_char_from_popup_input_command(&mut self) { 
         self.popup_input_command.pop();
     }
+    pub fn add_char_to_popup_input_shell(&mut self, ch: char) { 
+        self.popup_input_shell.push(ch);
+    }
+    pub fn remove_char_from_popup_input_shell(&mut self) { 
+        self.popup_input_shell.pop();
+    }
+    pub fn add_char_to_popup_input_category(&mut self, ch: char) { 
+        self.popup_input_category.push(ch);
+    }
+    pub fn remove_char_from_popup_input_category(&mut self) { 
+        self.popup_input_category.pop();
+    }
+    // Left/Right on the category field step through the existing categories.
+    pub fn cycle_popup_input_category(&mut self, forward: bool) {
+        let mut options = vec![String::new()];
+        options.extend(self.categories.iter().cloned());
+        let current = options.iter().position(|option| *option == self.popup_input_category.trim()).unwrap_or(0);
+        let next = if forward { (current + 1) % options.len() } else { (current + options.len() - 1) % options.len() };
+        self.popup_input_category = options[next].clone();
+    }
+    pub fn add_char_to_popup_input_tags(&mut self, ch: char) { 
+        self.popup_input_tags.push(ch);
+    }
+    pub fn remove_char_from_popup_input_tags(&mut self) { 
+        self.popup_input_tags.pop();
+    }
+    pub fn add_char_to_popup_input_workdir(&mut self, ch: char) { 
+        self.popup_input_workdir.push(ch);
+    }
+    pub fn remove_char_from_popup_input_workdir(&mut self) { 
+        self.popup_input_workdir.pop();
+    }
+    pub fn add_char_to_popup_input_env(&mut self, ch: char) { 
+        self.popup_input_env.push(ch);
+        self.popup_error = None;
+    }
+    pub fn remove_char_from_popup_input_env(&mut self) { 
+        self.popup_input_env.pop();
+        self.popup_error = None;
+    }
     pub fn next_popup_input_focus(&mut self) {
-        self.popup_input_focused = (self.popup_input_focused + 1) % 3; 
+        self.popup_input_focused = (self.popup_input_focused + 1) % 8; 
+    }
+
+    fn popup_input_shell_option(&self) -> Option<String> {
+        let shell = self.popup_input_shell.trim();
+        if shell.is_empty() { None } else { Some(shell.to_string()) }
+    }
+
+    
+    pub fn show_params_form(&mut self, command: BashCmd) {
+        self.params = template::parse_placeholders(&command.command)
+            .into_iter()
+            .map(|placeholder| ParamField::new(placeholder, &command, &self.config))
+            .collect();
+        self.param_focused = 0;
+        self.params_command = Some(command);
+        self.show_params_popup = true;
+    }
+
+    // Called on every tick of the event loop; fills in the pick lists of sources that finished.
+    pub fn poll_params(&mut self) {
+        for param in &mut self.params {
+            param.poll_source();
+        }
+    }
+
+    pub fn hide_params_form(&mut self) {
+        self.show_params_popup = false;
+        self.params_run_mode = RunMode::Exec;
+        self.params.clear();
+        self.param_focused = 0;
+        self.params_command = None;
+    }
+
+    fn param_values(&self) -> HashMap<String, String> {
+        self.params
+            .iter()
+            .map(|param| (param.placeholder.name.clone(), param.resolved_value()))
+            .collect()
+    }
+
+    pub fn expanded_params_command(&self) -> String {
+        let values = self.param_values();
+        match &self.params_command {
+            Some(command) => template::expand(&command.command, &values),
+            None => String::new(),
+        }
+    }
+
+    pub fn show_output_pane(&self) -> bool {
+        self.running.is_some() || self.run_error.is_some()
+    }
+
+    pub fn is_command_running(&self) -> bool {
+        self.running.as_ref().is_some_and(RunningCommand::is_running)
+    }
+
+    // Starts the command as a child whose output streams into the output pane. Only one command
+    // runs at a time; a finished one is replaced.
+    pub fn run_inside(&mut self, command: BashCmd) {
+        if self.is_command_running() {
+            return;
+        }
+        self.output_scroll = 0;
+        let params = std::mem::take(&mut self.chosen_params);
+        match RunningCommand::start(&command, &self.config) {
+            Ok(running) => {
+                self.running = Some(running);
+                self.running_entry = Some(HistoryEntry::start(&command, Invocation::Inside, params, &self.config));
+                self.run_error = None;
+            }
+            Err(err) => {
+                self.running = None;
+                self.run_error = Some(format!("{}: {}", command.name, err));
+            }
+        }
+    }
+
+    // Called on every tick of the event loop; records the run in the history once it ends.
+    pub fn poll_running(&mut self) {
+        let Some(running) = self.running.as_mut() else {
+            return;
+        };
+        if !running.poll() {
+            return;
+        }
+        let exit_code = running.finished.map(|(code, _)| code);
+        let Some(entry) = self.running_entry.take() else {
+            return;
+        };
+        if let Err(err) = history::record_execution(&entry.finish(exit_code)) {
+            running.lines.push(OutputLine { text: format!("Failed to record history: {}", err), is_stderr: true });
+        }
+    }
+
+    pub fn kill_running(&mut self) {
+        if let Some(running) = self.running.as_mut() {
+            running.kill();
+        }
+    }
+
+    pub fn close_output_pane(&mut self) {
+        if !self.is_command_running() {
+            self.running = None;
+            self.run_error = None;
+            self.output_scroll = 0;
+        }
+    }
+
+    pub fn scroll_output(&mut self, up: bool, lines: usize) {
+        let total = self.running.as_ref().map_or(0, |running| running.lines.len());
+        self.output_scroll = if up {
+            (self.output_scroll + lines).min(total)
+        } else {
+            self.output_scroll.saturating_sub(lines)
+        };
+    }
+
+    pub fn has_live_terminal(&self) -> bool {
+        self.terminal_session.as_ref().is_some_and(PtySession::is_running)
+    }
+
+    // Starts the command in the embedded terminal, sized to the whole screen until the first
+    // draw fits it to the pane. Only one terminal session exists at a time.
+    pub fn open_terminal(&mut self, command: BashCmd) {
+        if self.has_live_terminal() {
+            self.terminal_attached = true;
+            return;
+        }
+        let (cols, rows) = crossterm::terminal::size().unwrap_or((80, 24));
+        let params = std::mem::take(&mut self.chosen_params);
+        match PtySession::start(&command, &self.config, rows, cols) {
+            Ok(session) => {
+                self.terminal_session = Some(session);
+                self.terminal_entry = Some(HistoryEntry::start(&command, Invocation::Terminal, params, &self.config));
+                self.terminal_attached = true;
+            }
+            Err(err) => {
+                self.run_error = Some(format!("{}: {}", command.name, err));
+            }
+        }
+    }
+
+    pub fn attach_terminal(&mut self) {
+        if self.terminal_session.is_some() {
+            self.terminal_attached = true;
+        }
+    }
+
+    pub fn detach_terminal(&mut self) {
+        self.terminal_attached = false;
+        if !self.has_live_terminal() {
+            self.terminal_session = None;
+        }
+    }
+
+    // Records the session in the history once its command exits.
+    pub fn poll_terminal(&mut self) {
+        let Some(session) = self.terminal_session.as_mut() else {
+            return;
+        };
+        if !session.poll() {
+            return;
+        }
+        let exit_code = session.exit.as_ref().map(|(code, _)| *code);
+        if let Some(entry) = self.terminal_entry.take() {
+            if let Err(err) = history::record_execution(&entry.finish(exit_code)) {
+                self.run_error = Some(format!("Failed to record history: {}", err));
+            }
+        }
+        // A session that ends while detached has nothing left to show.
+        if !self.terminal_attached {
+            self.terminal_session = None;
+        }
+    }
+
+    // Returns the command with every placeholder substituted, ready to be executed.
+    pub fn finish_params_form(&mut self) -> Option<BashCmd> {
+        let expanded = self.expanded_params_command();
+        let values = self.param_values();
+        let mut command = self.params_command.take()?;
+        command.command = expanded;
+        self.chosen_params = values.into_iter().collect();
+        self.hide_params_form();
+        Some(command)
+    }
+
+    // Runs an expanded command in the given mode. Exec hands the command back to the caller,
+    // which leaves the TUI to run it.
+    pub fn start_run(&mut self, command: BashCmd, mode: RunMode) -> Option<BashCmd> {
+        match mode {
+            RunMode::Exec => return Some(command),
+            RunMode::Inside => self.run_inside(command),
+            RunMode::Terminal => self.open_terminal(command),
+            RunMode::Preview => self.show_preview(command),
+        }
+        None
+    }
+
+    // Runs a command picked from the list, asking for its placeholder values first if it has any.
+    pub fn run_selected(&mut self, mut command: BashCmd, mode: RunMode) -> Option<BashCmd> {
+        if template::has_placeholders(&command.command) {
+            self.show_params_form(command);
+            self.params_run_mode = mode;
+            return None;
+        }
+        command.command = template::unescape(&command.command);
+        self.request_run(command, mode)
+    }
+
+    // Like `start_run`, but a command flagged by the danger rules waits for confirmation first.
+    pub fn request_run(&mut self, command: BashCmd, mode: RunMode) -> Option<BashCmd> {
+        let warnings = danger::warnings(&command, &self.config.danger);
+        if warnings.is_empty() || mode == RunMode::Preview {
+            return self.start_run(command, mode);
+        }
+        self.confirm_warnings = warnings;
+        self.confirm_run_mode = mode;
+        self.confirm_input.clear();
+        self.confirm_command = Some(command);
+        None
+    }
+
+    // The config the chosen command runs with: a replay uses the profile of the run it repeats.
+    pub fn run_config(&self) -> Config {
+        let mut config = self.config.clone();
+        if let Some(entry) = &self.replay_entry {
+            config.profile = entry.profile.clone();
+        }
+        config
+    }
+
+    fn show_preview(&mut self, command: BashCmd) {
+        let replay_cwd = self.replay_entry.as_ref().and_then(|entry| entry.cwd.as_ref());
+        self.preview_lines = match execute::dry_run(&command, &self.run_config()) {
+            Ok(mut dry_run) => {
+                // A replay enters the directory of the original run first, unless the command
+                // has its own.
+                if let (Some(cwd), None) = (replay_cwd, &command.workdir) {
+                    dry_run.cwd = Some(cwd.into());
+                }
+                dry_run.lines()
+            }
+            Err(err) => vec![format!("Cannot run: {}", err)],
+        };
+        self.preview_warnings = danger::warnings(&command, &self.config.danger);
+        self.preview_command = Some(command);
+    }
+
+    pub fn hide_preview(&mut self) {
+        self.preview_command = None;
+        self.preview_lines.clear();
+        self.preview_warnings.clear();
+        self.chosen_params.clear();
+        self.replay_entry = None;
     }
-}
\ No newline at end of file
+
+    // Runs the previewed command after all, through the usual confirmation.
+    pub fn run_previewed(&mut self) -> Option<BashCmd> {
+        let command = self.preview_command.take()?;
+        self.preview_lines.clear();
+        self.preview_warnings.clear();
+        self.request_run(command, RunMode::Exec)
+    }
+
+    pub fn confirmation_matches(&self) -> bool {
+        self.confirm_command.as_ref().is_some_and(|command| self.confirm_input == danger::confirmation_word(command))
+    }
+
+    pub fn confirm_run(&mut self) -> Option<BashCmd> {
+        if !self.confirmation_matches() {
+            return None;
+        }
+        let command = self.confirm_command.take()?;
+        let mode = self.confirm_run_mode;
+        self.confirm_warnings.clear();
+        self.confirm_input.clear();
+        self.start_run(command, mode)
+    }
+
+    pub fn cancel_confirm(&mut self) {
+        self.confirm_command = None;
+        self.confirm_warnings.clear();
+        self.confirm_input.clear();
+        // Values kept for the history entry of the run that didn't happen.
+        self.chosen_params.clear();
+        self.replay_entry = None;
+    }
+
+    pub fn add_char_to_confirm_input(&mut self, ch: char) {
+        self.confirm_input.push(ch);
+    }
+
+    pub fn remove_char_from_confirm_input(&mut self) {
+        self.confirm_input.pop();
+    }
+
+    pub fn show_history_browser(&mut self) {
+        match history::load_history() {
+            Ok(mut entries) => {
+                history::fill_missing_names(&mut entries, &self.items);
+                self.history_entries = entries.into_iter().enumerate().map(|(index, entry)| (index + 1, entry)).rev().collect();
+                self.history_popup_state.select(if self.history_entries.is_empty() { None } else { Some(0) });
+                self.show_history_popup = true;
+            }
+            Err(err) => self.run_error = Some(format!("Failed to load history: {}", err)),
+        }
+    }
+
+    pub fn hide_history_browser(&mut self) {
+        self.show_history_popup = false;
+        self.history_entries.clear();
+    }
+
+    pub fn next_history_entry(&mut self) {
+        if let Some(selected) = self.history_popup_state.selected() {
+            self.history_popup_state.select(Some((selected + 1).min(self.history_entries.len() - 1)));
+        }
+    }
+
+    pub fn previous_history_entry(&mut self) {
+        if let Some(selected) = self.history_popup_state.selected() {
+            self.history_popup_state.select(Some(selected.saturating_sub(1)));
+        }
+    }
+
+    pub fn selected_history_entry(&self) -> Option<&(usize, HistoryEntry)> {
+        self.history_entries.get(self.history_popup_state.selected()?)
+    }
+
+    // The selected run as a command to execute again. The entry is kept until the replay starts
+    // or is cancelled: its parameter values go into the new history entry, and its directory and
+    // profile are used for the replay.
+    pub fn replay_selected_history_entry(&mut self) -> Option<BashCmd> {
+        let (_, entry) = self.selected_history_entry()?.clone();
+        self.hide_history_browser();
+        let replay = history::replay_command(&entry, &self.items).and_then(|command| match &entry.profile {
+            Some(profile) if !self.config.profiles.contains_key(profile) => Err(format!("profile '{}' is no longer defined", profile)),
+            _ => Ok(command),
+        });
+        match replay {
+            Ok(command) => {
+                self.chosen_params = entry.params.clone();
+                self.replay_entry = Some(entry);
+                Some(command)
+            }
+            Err(err) => {
+                self.run_error = Some(format!("Cannot replay: {}", err));
+                None
+            }
+        }
+    }
+
+    pub fn show_profile_switcher(&mut self) {
+        let active = self.config.profile.as_ref()
+            .and_then(|profile| self.config.profiles.keys().position(|name| name == profile));
+        self.profile_popup_state.select(Some(active.map_or(0, |index| index + 1)));
+        self.show_profile_popup = true;
+    }
+
+    pub fn hide_profile_switcher(&mut self) {
+        self.show_profile_popup = false;
+    }
+
+    pub fn next_profile(&mut self) {
+        let len = self.config.profiles.len() + 1;
+        let i = self.profile_popup_state.selected().map_or(0, |i| (i + 1) % len);
+        self.profile_popup_state.select(Some(i));
+    }
+
+    pub fn previous_profile(&mut self) {
+        let len = self.config.profiles.len() + 1;
+        let i = self.profile_popup_state.selected().map_or(0, |i| (i + len - 1) % len);
+        self.profile_popup_state.select(Some(i));
+    }
+
+    // Makes the highlighted profile active for every command run from now on.
+    pub fn select_highlighted_profile(&mut self) {
+        let profile = self.profile_popup_state.selected()
+            .and_then(|i| i.checked_sub(1))
+            .and_then(|i| self.config.profile_names().get(i).cloned());
+        self.config.profile = profile;
+        self.hide_profile_switcher();
+    }
+
+    pub fn focused_param(&self) -> Option<&ParamField> {
+        self.params.get(self.param_focused)
+    }
+
+    pub fn add_char_to_param_input(&mut self, ch: char) {
+        if let Some(param) = self.params.get_mut(self.param_focused) {
+            param.value.push(ch);
+            param.update_option_selection();
+        }
+    }
+    pub fn remove_char_from_param_input(&mut self) {
+        if let Some(param) = self.params.get_mut(self.param_focused) {
+            param.value.pop();
+            param.update_option_selection();
+        }
+    }
+    pub fn next_param_focus(&mut self) {
+        if !self.params.is_empty() {
+            self.param_focused = (self.param_focused + 1) % self.params.len();
+        }
+    }
+    pub fn previous_param_focus(&mut self) {
+        if !self.params.is_empty() {
+            self.param_focused = (self.param_focused + self.params.len() - 1) % self.params.len();
+        }
+    }
+
+    // Up/Down pick from the list when the focused placeholder has a source, otherwise they move focus.
+    pub fn param_down(&mut self) {
+        match self.params.get_mut(self.param_focused) {
+            Some(param) if param.has_source() => param.next_option(),
+            _ => self.next_param_focus(),
+        }
+    }
+    pub fn param_up(&mut self) {
+        match self.params.get_mut(self.param_focused) {
+            Some(param) if param.has_source() => param.previous_option(),
+            _ => self.previous_param_focus(),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(id: u64, name: &str) -> BashCmd {
+        BashCmd {
+            id,
+            name: name.to_string(),
+            command: format!("echo {}", name),
+            ..Default::default()
+        }
+    }
+
+    fn app_with(names: &[&str]) -> App {
+        let items = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| command(i as u64 + 1, name))
+            .collect();
+        App::new(Config::default(), CommandStore { commands: items, ..Default::default() })
+    }
+
+    fn type_filter(app: &mut App, text: &str) {
+        for ch in text.chars() {
+            app.add_char_to_filter(ch);
+        }
+    }
+
+    #[test]
+    fn editing_while_filtered_updates_the_filtered_command() {
+        let mut app = app_with(&["alpha", "beta", "gamma"]);
+        type_filter(&mut app, "gam");
+        app.next();
+        assert_eq!(app.selected_command().unwrap().name, "gamma");
+
+        app.enter_edit_mode(app.selected_command_id().unwrap());
+        assert_eq!(app.popup_input_name, "gamma");
+        app.popup_input_command = "echo edited".to_string();
+        app.save_command();
+
+        assert_eq!(app.command_by_id(3).unwrap().command, "echo edited");
+        assert_eq!(app.command_by_id(1).unwrap().command, "echo alpha");
+        assert_eq!(app.command_by_id(2).unwrap().command, "echo beta");
+    }
+
+    #[test]
+    fn deleting_while_filtered_removes_the_filtered_command() {
+        let mut app = app_with(&["alpha", "beta", "gamma"]);
+        type_filter(&mut app, "beta");
+        app.next();
+
+        app.request_delete();
+        app.confirm_delete();
+
+        let names: Vec<&str> = app.items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, ["alpha", "gamma"]);
+    }
+
+    #[test]
+    fn typed_param_value_wins_over_a_fuzzy_matching_option() {
+        let placeholder = Placeholder { name: "branch".to_string(), default: None, description: None, source: None };
+        let mut field = ParamField {
+            placeholder,
+            value: String::new(),
+            options: vec!["feature/main-menu".to_string(), "main".to_string()],
+            option_state: ListState::default(),
+            source_error: None,
+            loading: None,
+        };
+        field.update_option_selection();
+        assert_eq!(field.resolved_value(), "feature/main-menu");
+
+        field.value = "mn".to_string();
+        field.update_option_selection();
+        assert_eq!(field.resolved_value(), "mn");
+        field.next_option();
+        assert_eq!(field.resolved_value(), *field.filtered_options()[0]);
+    }
+
+    #[test]
+    fn new_commands_get_unique_ids_past_255_entries() {
+        let names: Vec<String> = (0..300).map(|i| format!("cmd{}", i)).collect();
+        let name_refs: Vec<&str> = names.iter().map(String::as_str).collect();
+        let mut app = app_with(&name_refs);
+
+        app.show_add_popup();
+        app.popup_input_name = "new".to_string();
+        app.popup_input_command = "true".to_string();
+        app.save_command();
+        app.duplicate_command(1);
+
+        let mut ids: Vec<u64> = app.items.iter().map(|item| item.id).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 302);
+        assert_eq!(app.items.last().unwrap().id, 301);
+        assert_eq!(app.items[1].id, 302);
+    }
+
+    #[test]
+    fn renaming_and_deleting_a_category_carries_its_commands() {
+        let mut app = app_with(&["alpha", "beta", "gamma"]);
+        app.items[0].category = "ops".to_string();
+        app.items[1].category = "ops".to_string();
+        app.items[2].category = "dev".to_string();
+        app.categories = vec!["ops".to_string(), "dev".to_string()];
+
+        assert!(!app.rename_category("ops", "dev"));
+        assert!(app.rename_category("ops", " infra "));
+        assert_eq!(app.categories, ["infra", "dev"]);
+        let categories: Vec<&str> = app.items.iter().map(|item| item.category.as_str()).collect();
+        assert_eq!(categories, ["infra", "infra", "dev"]);
+
+        app.delete_category("infra");
+        assert_eq!(app.categories, ["dev"]);
+        assert_eq!(app.items.len(), 3);
+        assert!(CategoryFilter::Uncategorized.matches(&app.items[0]));
+        assert!(CategoryFilter::Uncategorized.matches(&app.items[1]));
+        assert_eq!(app.items[2].category, "dev");
+    }
+
+    #[test]
+    fn env_with_spaces_and_quotes_survives_a_round_trip() {
+        let env: BTreeMap<String, String> = [
+            ("GIT_SSH_COMMAND", "ssh -i key -o Opt=yes"),
+            ("KUBECONFIG", "~/.kube/$CLUSTER"),
+            ("EMPTY", ""),
+            ("QUOTE", "it's \"quoted\""),
+            ("PLAIN", "a=b"),
+        ].into_iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        let formatted = format_env(&env);
+        assert!(formatted.contains("PLAIN=a=b"), "{formatted}");
+        assert_eq!(parse_env(&formatted).unwrap(), env);
+
+        let typed = parse_env(r#"A="x y" B=c\ d C='e f'"#).unwrap();
+        assert_eq!(typed["A"], "x y");
+        assert_eq!(typed["B"], "c d");
+        assert_eq!(typed["C"], "e f");
+        assert!(parse_env("A='open").is_err());
+        assert!(parse_env("-i").is_err());
+    }
+}
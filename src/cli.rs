@@ -1,9 +1,225 @@
 
+use std::collections::{BTreeMap, HashMap};
 use std::env;
-use std::io::{self, Write};
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
 
-use crate::commands::append_command_to_file;
-use crate::app::BashCmd;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::commands::{self, append_command_to_file, find_command_index, load_command_store, write_command_store, CommandStore};
+use crate::app::{format_env, parse_env, parse_env_assignment, parse_tags, BashCmd};
+use crate::config::{load_config, Config};
+use crate::history::{HistoryEntry, Invocation};
+use crate::output::{format_command, format_commands, format_history, OutputFormat};
+use crate::query::{parse_query, score_command};
+use crate::{danger, execute, history, template};
+
+#[derive(Parser)]
+#[command(name = "bash-commander", version, about = "Save, search and run shell commands")]
+#[command(after_help = "Any other first argument saves the whole command line, e.g. `bash-commander du -sh '*'`.\nWithout arguments the interactive picker opens.")]
+pub struct Cli {
+    /// Environment profile from config.toml for tui, emit, run and replay
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+#[derive(Subcommand)]
+pub enum CliCommand {
+    /// Open the interactive picker (the default without arguments)
+    Tui,
+    /// Save a command, prompting for the name and description unless --name is given
+    Add(AddArgs),
+    /// List saved commands
+    List {
+        /// Only commands in this category
+        #[arg(short, long)]
+        category: Option<String>,
+        /// Only commands carrying this tag
+        #[arg(short, long)]
+        tag: Option<String>,
+        /// Output format; json and tsv follow docs/output-format.md
+        #[arg(short, long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Search saved commands using the filter box syntax (#tag, -#tag, name:, desc:, cmd:)
+    Search {
+        #[arg(required = true)]
+        query: Vec<String>,
+        /// Output format; json and tsv follow docs/output-format.md
+        #[arg(short, long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Run a saved command, given by name or ID
+    Run {
+        command: String,
+        /// Value for a template placeholder, overriding $BASH_COMMANDER_PARAM_<NAME>
+        #[arg(short, long = "param", value_name = "NAME=VALUE")]
+        params: Vec<String>,
+        /// Never prompt: placeholders without a value fall back to their default and dangerous
+        /// commands run without typed confirmation
+        #[arg(short, long)]
+        yes: bool,
+        /// Print what would run (expanded command, shell, directory, environment) and exit
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Print every field of a saved command
+    Show {
+        command: String,
+        /// Output format; json and tsv follow docs/output-format.md
+        #[arg(short, long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// List past runs, oldest first, numbered for `replay`
+    History {
+        /// Only runs of this saved command (name or ID)
+        command: Option<String>,
+        /// Only the last N runs
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+        /// Output format; json and tsv follow docs/output-format.md
+        #[arg(short, long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Run an entry of `history` again with the same parameter values and directory
+    Replay {
+        /// Entry number from `history`; the latest run when omitted
+        number: Option<usize>,
+        /// Run dangerous commands without typed confirmation
+        #[arg(short, long)]
+        yes: bool,
+        /// Print what would run (command, shell, directory, environment) and exit
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Change a saved command; prompts for every field when no flag is given
+    Edit(EditArgs),
+    /// Delete a saved command
+    Rm {
+        command: String,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Add the commands from a file written by `export` ("-" reads stdin)
+    Import {
+        path: PathBuf,
+    },
+    /// Write all saved commands as TOML
+    Export {
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Open the picker and print the chosen command instead of running it
+    Emit {
+        /// Write the command to this file descriptor
+        #[arg(long, conflicts_with = "output")]
+        fd: Option<u32>,
+        /// Write the command to this file
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Print the shell integration snippet for bash, zsh or fish
+    Init {
+        shell: String,
+    },
+    /// Save the previous command line (called by the shell hooks); reads stdin without an argument
+    SaveLast {
+        #[arg(allow_hyphen_values = true)]
+        command: Option<String>,
+    },
+    /// Pick commands from the bash, zsh and fish history files and save them
+    ImportHistory,
+    /// Print a completion script for bash, zsh or fish
+    Completions {
+        shell: String,
+    },
+    // Called by the completion scripts for the parts that depend on the command store.
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        #[arg(value_enum)]
+        target: CompletionTarget,
+        command: Option<String>,
+    },
+    #[command(external_subcommand)]
+    Save(Vec<String>),
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum CompletionTarget {
+    // Names of all saved commands.
+    Names,
+    // Placeholder names of one command.
+    Params,
+    // Environment profiles defined in config.toml, for --profile.
+    Profiles,
+}
+
+#[derive(Args)]
+pub struct AddArgs {
+    #[arg(short, long)]
+    pub name: Option<String>,
+    #[arg(short, long)]
+    pub desc: Option<String>,
+    #[arg(short, long)]
+    pub category: Option<String>,
+    /// Comma- or space-separated tags
+    #[arg(short, long)]
+    pub tags: Option<String>,
+    /// Shell to run the command with instead of the configured one
+    #[arg(long)]
+    pub shell: Option<String>,
+    /// Ask for typed confirmation before every run
+    #[arg(long)]
+    pub always_confirm: bool,
+    /// Directory to run the command in; ~ and $VARIABLES are expanded at run time
+    #[arg(short, long)]
+    pub workdir: Option<String>,
+    /// Environment variable for the command, repeatable
+    #[arg(short, long = "env", value_name = "NAME=VALUE")]
+    pub env: Vec<String>,
+    /// The command text; several words are joined with spaces
+    #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+    pub command: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct EditArgs {
+    pub command: String,
+    #[arg(short, long)]
+    pub name: Option<String>,
+    #[arg(short, long)]
+    pub desc: Option<String>,
+    /// New command text
+    #[arg(long = "command", value_name = "TEXT", allow_hyphen_values = true)]
+    pub text: Option<String>,
+    /// New category, "" for none
+    #[arg(short, long)]
+    pub category: Option<String>,
+    /// New comma- or space-separated tags, "" for none
+    #[arg(short, long)]
+    pub tags: Option<String>,
+    /// New shell, "" for the configured one
+    #[arg(long)]
+    pub shell: Option<String>,
+    /// Whether every run asks for typed confirmation
+    #[arg(long, value_name = "BOOL")]
+    pub always_confirm: Option<bool>,
+    /// New working directory, "" for the current one
+    #[arg(short, long)]
+    pub workdir: Option<String>,
+    /// Set an environment variable for the command, repeatable
+    #[arg(short, long = "env", value_name = "NAME=VALUE")]
+    pub env: Vec<String>,
+    /// Remove an environment variable of the command, repeatable
+    #[arg(long, value_name = "NAME")]
+    pub unset_env: Vec<String>,
+}
 
 pub fn get_user_input(label: &str) -> String {
     print!("{}:", label);
@@ -14,14 +230,45 @@ pub fn get_user_input(label: &str) -> String {
         .read_line(&mut input)
         .expect("Failed to read line");
 
-    return input.trim().to_string();
+    input.trim().to_string()
+}
+
+// Prompt that keeps `current` when the answer is empty.
+fn get_user_input_or(label: &str, current: &str) -> String {
+    let input = get_user_input(&format!("{} [{}]", label, current));
+    if input.is_empty() { current.to_string() } else { input }
+}
+
+// Bare-argument shorthand: `bash-commander ls -la` saves `ls -la` after the usual prompts.
+pub fn text_flow(args: &[String]) {
+    let command_text: String = args.join(" ");
+    save_flow(command_text);
 }
 
-pub fn text_flow() {
-    let args: Vec<String> = env::args().collect();
+// `save-last [COMMAND]`: the shell hooks pass the previous command line as one argument, so it
+// arrives exactly as typed; without an argument it is read from stdin.
+pub fn save_last_flow(command: Option<String>) {
+    let command_text = match command {
+        Some(command_text) => command_text,
+        None => {
+            let mut input = String::new();
+            if let Err(err) = io::stdin().read_to_string(&mut input) {
+                eprintln!("Failed to read command: {}", err);
+                return;
+            }
+            input
+        }
+    };
 
-    let command_text: String = args[1..].join(" ");
+    let command_text = command_text.trim().to_string();
+    if command_text.is_empty() {
+        eprintln!("No command to save.");
+        return;
+    }
+    save_flow(command_text);
+}
 
+fn save_flow(command_text: String) {
     println!("Command: {}", command_text);
     let name = get_user_input("Name");
     let description = get_user_input("Description");
@@ -30,21 +277,426 @@ pub fn text_flow() {
         name,
         desc: description,
         command: command_text,
-        index: 100, 
-        category: 1, 
-        favourite: false, 
+        favourite: false,
+        ..Default::default()
     };
 
     let confirmation = get_user_input("Do you want to save this command? (y/n)");
-    
-    if confirmation.to_lowercase() == "y" {
-            append_command_to_file(&bash_cmd);
 
-    println!("command saved.");
+    if confirmation.to_lowercase() == "y" {
+        match append_command_to_file(&bash_cmd) {
+            Ok(()) => println!("command saved."),
+            Err(err) => eprintln!("Failed to save command: {}", err),
+        }
     } else {
         println!("Command not saved.");
+    }
+}
+
+fn non_empty(value: String) -> Option<String> {
+    Some(value).filter(|value| !value.trim().is_empty())
+}
+
+// With --name nothing is asked, so scripts can add commands; otherwise prompt like the shorthand.
+pub fn add_flow(args: AddArgs) -> Result<(), Box<dyn Error>> {
+    let command_text = args.command.join(" ");
+    let (name, desc) = match args.name {
+        Some(name) => (name, args.desc.unwrap_or_default()),
+        None => {
+            println!("Command: {}", command_text);
+            let name = get_user_input("Name");
+            (name, args.desc.unwrap_or_else(|| get_user_input("Description")))
+        }
+    };
+    let env = args.env.iter().map(|assignment| parse_env_assignment(assignment)).collect::<Result<_, _>>()?;
+    let workdir = args.workdir.and_then(non_empty);
+    let always_confirm = args.always_confirm;
+    add_command(BashCmd { name, desc, always_confirm, workdir, env, ..command_from_args(command_text, args.category, args.tags, args.shell) })
+}
+
+fn command_from_args(command: String, category: Option<String>, tags: Option<String>, shell: Option<String>) -> BashCmd {
+    BashCmd {
+        command,
+        category: category.unwrap_or_default().trim().to_string(),
+        tags: tags.as_deref().map(parse_tags).unwrap_or_default(),
+        shell: shell.and_then(non_empty),
+        ..Default::default()
+    }
+}
+
+fn add_command(command: BashCmd) -> Result<(), Box<dyn Error>> {
+    if command.name.trim().is_empty() || command.command.trim().is_empty() {
+        return Err("a command needs a name and command text".into());
+    }
+    let mut store = load_command_store()?;
+    let id = commands::next_command_id(&store.commands);
+    store.commands.push(BashCmd { id, ..command });
+    write_command_store(&store.categories, &store.commands)?;
+    println!("Saved command {}.", id);
+    Ok(())
+}
+
+pub fn list_flow(category: Option<String>, tag: Option<String>, format: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let store = load_command_store()?;
+    let listed: Vec<&BashCmd> = store.commands
+        .iter()
+        .filter(|command| category.as_ref().is_none_or(|category| &command.category == category))
+        .filter(|command| tag.as_ref().is_none_or(|tag| command.tags.contains(tag)))
+        .collect();
+    print!("{}", format_commands(&listed, format));
+    Ok(())
+}
+
+// Same matching and ranking as the TUI filter box, best match first. Like grep, finding nothing
+// is an error, after printing the (empty) result so scripts still get valid JSON or TSV.
+pub fn search_flow(query: &[String], format: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let store = load_command_store()?;
+    let terms = parse_query(&query.join(" "));
+    let mut scored: Vec<(i64, &BashCmd)> = store.commands
+        .iter()
+        .filter_map(|command| score_command(command, &terms).map(|(score, _)| (score, command)))
+        .collect();
+    scored.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+    let found: Vec<&BashCmd> = scored.into_iter().map(|(_, command)| command).collect();
+    print!("{}", format_commands(&found, format));
+    if found.is_empty() {
+        return Err("no matching commands".into());
+    }
+    Ok(())
+}
+
+pub fn show_flow(key: &str, format: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let store = load_command_store()?;
+    let command = &store.commands[find_command_index(&store.commands, key)?];
+    print!("{}", format_command(command, format));
+    Ok(())
+}
+
+// `--param name=value` arguments of `run`.
+fn parse_params(params: &[String]) -> Result<HashMap<String, String>, String> {
+    params
+        .iter()
+        .map(|param| match param.split_once('=') {
+            Some((name, value)) if !name.trim().is_empty() => Ok((name.trim().to_string(), value.to_string())),
+            _ => Err(format!("invalid --param '{}', expected NAME=VALUE", param)),
+        })
+        .collect()
+}
+
+const PARAM_ENV_PREFIX: &str = "BASH_COMMANDER_PARAM_";
+
+// `{{pod-name}}` is read from $BASH_COMMANDER_PARAM_POD_NAME.
+fn param_env_var(name: &str) -> String {
+    let suffix: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    format!("{}{}", PARAM_ENV_PREFIX, suffix)
+}
+
+// Values come from --param, then the environment, then a prompt on the terminal (defaults shown
+// in brackets). With `yes` nothing is asked and a placeholder without a default is an error.
+fn resolve_params(command: &str, mut values: HashMap<String, String>, yes: bool) -> Result<HashMap<String, String>, String> {
+    for placeholder in template::parse_placeholders(command) {
+        if values.contains_key(&placeholder.name) {
+            continue;
+        }
+        let value = if let Ok(value) = env::var(param_env_var(&placeholder.name)) {
+            value
+        } else if yes {
+            placeholder.default.clone().ok_or_else(|| {
+                format!("no value for '{}', pass --param {}=VALUE or set {}", placeholder.name, placeholder.name, param_env_var(&placeholder.name))
+            })?
+        } else {
+            let mut label = placeholder.name.clone();
+            if let Some(description) = &placeholder.description {
+                label = format!("{} ({})", label, description);
+            }
+            get_user_input_or(&label, placeholder.default.as_deref().unwrap_or(""))
+        };
+        values.insert(placeholder.name, value);
+    }
+    Ok(values)
+}
+
+// Returns the exit code of the command so `main` can exit with it.
+pub fn run_flow(key: &str, params: &[String], yes: bool, dry_run: bool, config: &Config) -> Result<i32, Box<dyn Error>> {
+    let store = load_command_store()?;
+    let mut command = store.commands[find_command_index(&store.commands, key)?].clone();
+    let mut values = parse_params(params)?;
+    if template::has_placeholders(&command.command) {
+        values = resolve_params(&command.command, values, yes)?;
+        // Stray --param values that match no placeholder aren't worth recording.
+        let names: Vec<String> = template::parse_placeholders(&command.command).into_iter().map(|placeholder| placeholder.name).collect();
+        values.retain(|name, _| names.contains(name));
+    } else {
+        values.clear();
+    }
+    command.command = template::expand(&command.command, &values);
+    if dry_run {
+        print_dry_run(&command, config)?;
+        return Ok(0);
+    }
+    run_recorded(&command, values.into_iter().collect(), yes, config)
+}
+
+fn print_dry_run(command: &BashCmd, config: &Config) -> Result<(), Box<dyn Error>> {
+    for line in execute::dry_run(command, config)?.lines() {
+        println!("{}", line);
+    }
+    for warning in danger::warnings(command, &config.danger) {
+        println!("Needs confirmation: {}", warning);
+    }
+    Ok(())
+}
+
+// Commands flagged by the danger rules (or marked always confirm) only run once their name is
+// typed; with `yes` the warnings are printed and the command runs.
+fn confirm_dangerous(command: &BashCmd, yes: bool, config: &Config) -> Result<(), Box<dyn Error>> {
+    let warnings = danger::warnings(command, &config.danger);
+    if warnings.is_empty() {
+        return Ok(());
+    }
+    for warning in &warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    if yes {
+        return Ok(());
+    }
+    let word = danger::confirmation_word(command);
+    println!("{}", command.command);
+    if get_user_input(&format!("Type '{}' to run it", word)) != word {
+        return Err("not confirmed, nothing was run".into());
+    }
+    Ok(())
+}
+
+fn run_recorded(command: &BashCmd, params: BTreeMap<String, String>, yes: bool, config: &Config) -> Result<i32, Box<dyn Error>> {
+    confirm_dangerous(command, yes, config)?;
+    let entry = HistoryEntry::start(command, Invocation::Cli, params, config);
+    let exit_code = execute::run_command(command, config)?;
+    if let Err(err) = history::record_execution(&entry.finish(Some(exit_code))) {
+        eprintln!("Failed to record history: {}", err);
+    }
+    Ok(exit_code)
+}
+
+pub fn history_flow(key: Option<String>, limit: Option<usize>, format: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let store = load_command_store()?;
+    let mut entries = history::load_history()?;
+    history::fill_missing_names(&mut entries, &store.commands);
+    let command_id = match key {
+        Some(key) => Some(store.commands[find_command_index(&store.commands, &key)?].id),
+        None => None,
+    };
+    let mut numbered: Vec<(usize, &HistoryEntry)> = entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| (index + 1, entry))
+        .filter(|(_, entry)| command_id.is_none_or(|id| entry.command_id == id))
+        .collect();
+    if let Some(limit) = limit {
+        numbered.drain(..numbered.len().saturating_sub(limit));
+    }
+    print!("{}", format_history(&numbered, format));
+    Ok(())
+}
+
+// Runs the recorded text again from the directory it ran in, under the profile it ran with unless
+// `profile` overrides it, and records it as a new run.
+pub fn replay_flow(number: Option<usize>, profile: Option<String>, yes: bool, dry_run: bool, mut config: Config) -> Result<i32, Box<dyn Error>> {
+    let entries = history::load_history()?;
+    let entry = match number {
+        Some(number) => history::entry_by_number(&entries, number)?,
+        None => entries.last().ok_or("the history is empty")?,
+    };
+    let store = load_command_store()?;
+    let command = history::replay_command(entry, &store.commands)?;
+    config.set_profile(profile.or_else(|| entry.profile.clone()))?;
+    if let Some(cwd) = &entry.cwd {
+        env::set_current_dir(cwd).map_err(|err| format!("cannot enter {}: {}", cwd, err))?;
+    }
+    if dry_run {
+        print_dry_run(&command, &config)?;
+        return Ok(0);
+    }
+    eprintln!("{}", command.command);
+    run_recorded(&command, entry.params.clone(), yes, &config)
+}
+
+pub fn edit_flow(args: EditArgs) -> Result<(), Box<dyn Error>> {
+    let mut store = load_command_store()?;
+    let index = find_command_index(&store.commands, &args.command)?;
+    let command = &mut store.commands[index];
+
+    let has_flags = args.name.is_some() || args.desc.is_some() || args.text.is_some()
+        || args.category.is_some() || args.tags.is_some() || args.shell.is_some() || args.always_confirm.is_some()
+        || args.workdir.is_some() || !args.env.is_empty() || !args.unset_env.is_empty();
+    if has_flags {
+        if let Some(name) = args.name { command.name = name; }
+        if let Some(desc) = args.desc { command.desc = desc; }
+        if let Some(text) = args.text { command.command = text; }
+        if let Some(category) = args.category { command.category = category.trim().to_string(); }
+        if let Some(tags) = args.tags { command.tags = parse_tags(&tags); }
+        if let Some(shell) = args.shell { command.shell = non_empty(shell); }
+        if let Some(always_confirm) = args.always_confirm { command.always_confirm = always_confirm; }
+        if let Some(workdir) = args.workdir { command.workdir = non_empty(workdir); }
+        for assignment in &args.env {
+            let (name, value) = parse_env_assignment(assignment)?;
+            command.env.insert(name, value);
+        }
+        for name in &args.unset_env { command.env.remove(name); }
+    } else {
+        command.name = get_user_input_or("Name", &command.name);
+        command.desc = get_user_input_or("Description", &command.desc);
+        command.command = get_user_input_or("Command", &command.command);
+        command.category = get_user_input_or("Category", &command.category);
+        command.tags = parse_tags(&get_user_input_or("Tags", &command.tags.join(", ")));
+        command.shell = non_empty(get_user_input_or("Shell", command.shell.as_deref().unwrap_or("")));
+        command.workdir = non_empty(get_user_input_or("Working directory", command.workdir.as_deref().unwrap_or("")));
+        command.env = parse_env(&get_user_input_or("Environment (NAME=value ...)", &format_env(&command.env)))?;
+        let always_confirm = get_user_input_or("Always confirm (yes/no)", if command.always_confirm { "yes" } else { "no" });
+        command.always_confirm = match always_confirm.to_lowercase().as_str() {
+            "y" | "yes" | "true" => true,
+            "n" | "no" | "false" => false,
+            other => return Err(format!("expected yes or no for always confirm, got '{}'", other).into()),
+        };
+    }
+
+    if command.name.trim().is_empty() || command.command.trim().is_empty() {
+        return Err("a command needs a name and command text".into());
+    }
+    if !command.category.is_empty() && !store.categories.contains(&command.category) {
+        store.categories.push(command.category.clone());
+    }
+    write_command_store(&store.categories, &store.commands)?;
+    println!("Command saved.");
+    Ok(())
+}
+
+pub fn rm_flow(key: &str, yes: bool) -> Result<(), Box<dyn Error>> {
+    let mut store = load_command_store()?;
+    let index = find_command_index(&store.commands, key)?;
+    if !yes {
+        let confirmation = get_user_input(&format!("Delete '{}'? (y/n)", store.commands[index].name));
+        if confirmation.to_lowercase() != "y" {
+            println!("Command not deleted.");
+            return Ok(());
+        }
+    }
+    let removed = store.commands.remove(index);
+    write_command_store(&store.categories, &store.commands)?;
+    println!("Deleted command {}.", removed.id);
+    Ok(())
+}
+
+pub fn export_flow(output: Option<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let store = load_command_store()?;
+    let contents = toml::to_string_pretty(&CommandStore { version: commands::STORE_VERSION, ..store })?;
+    match output {
+        Some(path) => fs::write(path, contents)?,
+        None => io::stdout().write_all(contents.as_bytes())?,
+    }
+    Ok(())
+}
+
+// Imported commands get fresh IDs; ones whose text is already saved are skipped.
+pub fn import_flow(path: PathBuf) -> Result<(), Box<dyn Error>> {
+    let contents = if path.as_os_str() == "-" {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input)?;
+        input
+    } else {
+        fs::read_to_string(&path)?
+    };
+    let imported: CommandStore = toml::from_str(&contents)
+        .map_err(|err| format!("{}: {}", path.display(), err))?;
+    if imported.version > commands::STORE_VERSION {
+        return Err(format!("{} was written by a newer version (schema {})", path.display(), imported.version).into());
+    }
+
+    let mut store = load_command_store()?;
+    let mut added = 0;
+    for command in imported.commands {
+        if store.commands.iter().any(|saved| saved.command == command.command) {
+            continue;
+        }
+        let id = commands::next_command_id(&store.commands);
+        if !command.category.is_empty() && !store.categories.contains(&command.category) {
+            store.categories.push(command.category.clone());
+        }
+        store.commands.push(BashCmd { id, ..command });
+        added += 1;
+    }
+    for category in imported.categories {
+        if !store.categories.contains(&category) {
+            store.categories.push(category);
+        }
+    }
+    write_command_store(&store.categories, &store.commands)?;
+    println!("Imported {} commands.", added);
+    Ok(())
+}
+
+// Completion scripts swallow errors, so a broken store or unknown command just prints nothing.
+pub fn complete_flow(target: CompletionTarget, key: Option<String>) {
+    // Profiles live in config.toml, so they complete even when the store can't be read.
+    if let CompletionTarget::Profiles = target {
+        for name in load_config().map(|config| config.profile_names()).unwrap_or_default() {
+            println!("{}", name);
+        }
         return;
     }
+    let Ok(store) = load_command_store() else {
+        return;
+    };
+    match target {
+        CompletionTarget::Names => {
+            for command in &store.commands {
+                println!("{}", command.name);
+            }
+        }
+        CompletionTarget::Params => {
+            let Some(index) = key.and_then(|key| find_command_index(&store.commands, &key).ok()) else {
+                return;
+            };
+            for placeholder in template::parse_placeholders(&store.commands[index].command) {
+                println!("{}", placeholder.name);
+            }
+        }
+        CompletionTarget::Profiles => {}
+    }
+}
+
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-}
\ No newline at end of file
+    #[test]
+    fn param_env_vars_are_upper_cased_with_underscores() {
+        assert_eq!(param_env_var("pod-name"), "BASH_COMMANDER_PARAM_POD_NAME");
+        assert_eq!(param_env_var("ns_2"), "BASH_COMMANDER_PARAM_NS_2");
+    }
+
+    #[test]
+    fn params_come_from_flags_then_env_then_defaults() {
+        // Names unique to this test, as the environment is shared by all tests.
+        env::set_var(param_env_var("resolve-flag"), "from env");
+        env::set_var(param_env_var("resolve-env"), "from env");
+        env::set_var(param_env_var("resolve-env-default"), "from env");
+        let command = "echo {{resolve-flag}} {{resolve-env}} {{resolve-env-default=default}} {{resolve-default=default}}";
+        let flags = parse_params(&["resolve-flag=from flag".to_string()]).unwrap();
+
+        let values = resolve_params(command, flags, true).unwrap();
+        assert_eq!(values["resolve-flag"], "from flag");
+        assert_eq!(values["resolve-env"], "from env");
+        assert_eq!(values["resolve-env-default"], "from env");
+        assert_eq!(values["resolve-default"], "default");
+
+        let err = resolve_params("echo {{resolve-missing}}", HashMap::new(), true).unwrap_err();
+        assert!(err.contains("--param resolve-missing=VALUE"), "{err}");
+        assert!(err.contains("BASH_COMMANDER_PARAM_RESOLVE_MISSING"), "{err}");
+        assert!(parse_params(&["=value".to_string()]).is_err());
+    }
+}
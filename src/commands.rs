@@ -1,89 +1,244 @@
-
-
-
-
-
-
-
-
-
+use std::collections::HashSet;
 use std::io::{self, BufReader, BufRead, Write};
 use std::fs;
-use std::path::PathBuf;
-use dirs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
 
 use crate::app::BashCmd;
 
-pub fn get_commands_file_path() -> PathBuf {
+// Bump when the layout of commands.toml changes and add the upgrade step to `load_command_store`.
+// Version 2: categories are names (version 1 stored a meaningless number, read as uncategorized).
+pub const STORE_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct CommandStore {
+    pub version: u32,
+    // Kept separately from the commands so a category can exist before anything is assigned to it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<String>,
+    #[serde(default)]
+    pub commands: Vec<BashCmd>,
+}
+
+pub fn get_config_dir() -> PathBuf {
     let mut path = PathBuf::new();
     if let Some(home_dir) = dirs::home_dir() {
         path.push(home_dir);
         path.push(".config");
         path.push("bash_command_app");
-        fs::create_dir_all(&path).unwrap_or_else(|_| {});
-        path.push("commands.txt"); 
+        fs::create_dir_all(&path).unwrap_or(());
     }
     path
 }
 
-pub fn load_commands_from_file() -> io::Result<Vec<BashCmd>> {
+pub fn get_commands_file_path() -> PathBuf {
+    let mut path = get_config_dir();
+    path.push("commands.toml");
+    path
+}
+
+fn get_legacy_commands_file_path() -> PathBuf {
+    let mut path = get_config_dir();
+    path.push("commands.txt");
+    path
+}
+
+pub fn load_command_store() -> io::Result<CommandStore> {
+    migrate_legacy_commands_file()?;
+
     let path = get_commands_file_path();
     if !path.exists() {
-        return Ok(Vec::new());
+        return Ok(CommandStore { version: STORE_VERSION, ..Default::default() });
     }
 
-    let file = fs::File::open(path)?; 
-    let reader = BufReader::new(file); 
+    parse_command_store(&fs::read_to_string(&path)?, &path)
+}
+
+fn parse_command_store(contents: &str, path: &Path) -> io::Result<CommandStore> {
+    let mut store: CommandStore = toml::from_str(contents).map_err(|err| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), err))
+    })?;
+    if store.version > STORE_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} was written by a newer version (schema {}, supported {})", path.display(), store.version, STORE_VERSION),
+        ));
+    }
+
+    assign_missing_ids(&mut store.commands);
+    for command in &store.commands {
+        if !command.category.is_empty() && !store.categories.contains(&command.category) {
+            store.categories.push(command.category.clone());
+        }
+    }
+    Ok(store)
+}
+
+pub fn next_command_id(commands: &[BashCmd]) -> u64 {
+    commands.iter().map(|command| command.id).max().unwrap_or(0) + 1
+}
+
+// Entries written by hand or by older versions may lack an ID (deserialized as 0) or share one.
+fn assign_missing_ids(commands: &mut [BashCmd]) {
+    let first_free_id = next_command_id(commands);
+    let mut seen = HashSet::new();
+    let missing = commands.iter_mut().filter(|command| command.id == 0 || !seen.insert(command.id));
+    for (id, command) in (first_free_id..).zip(missing) {
+        command.id = id;
+    }
+}
+
+pub fn save_commands_to_file(app: &crate::app::App) -> io::Result<()> {
+    write_command_store(&app.categories, &app.items)
+}
+
+pub fn write_command_store(categories: &[String], commands: &[BashCmd]) -> io::Result<()> {
+    let store = CommandStore {
+        version: STORE_VERSION,
+        categories: categories.to_vec(),
+        commands: commands.to_vec(),
+    };
+    let contents = toml::to_string_pretty(&store)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    // Write next to the store and rename so a crash never leaves a half-written file behind.
+    let path = get_commands_file_path();
+    let tmp_path = path.with_extension("toml.tmp");
+    let mut file = fs::File::create(&tmp_path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(tmp_path, path)
+}
+
+pub fn append_command_to_file(command: &BashCmd) -> io::Result<()> {
+    append_commands_to_file(std::slice::from_ref(command))
+}
+
+pub fn append_commands_to_file(commands: &[BashCmd]) -> io::Result<()> {
+    let mut store = load_command_store()?;
+    for command in commands {
+        let id = next_command_id(&store.commands);
+        store.commands.push(BashCmd { id, ..command.clone() });
+    }
+    write_command_store(&store.categories, &store.commands)
+}
+
+// One-time upgrade from the `name※desc※command[※shell]` lines of commands.txt.
+fn migrate_legacy_commands_file() -> io::Result<()> {
+    let legacy_path = get_legacy_commands_file_path();
+    if get_commands_file_path().exists() || !legacy_path.exists() {
+        return Ok(());
+    }
+
+    let mut commands = load_legacy_commands(&legacy_path)?;
+    assign_missing_ids(&mut commands);
+    write_command_store(&[], &commands)?;
+    fs::rename(&legacy_path, legacy_path.with_extension("txt.migrated"))
+}
+
+fn load_legacy_commands(path: &Path) -> io::Result<Vec<BashCmd>> {
+    let file = fs::File::open(path)?;
+    let reader = BufReader::new(file);
 
     let mut commands = Vec::new();
-    for line_result in reader.lines() { 
-        let line = line_result?; 
-        let parts: Vec<&str> = line.splitn(3, '※').collect(); 
-        if parts.len() == 3 { 
-            let name = parts[0].trim().to_string(); 
-            let desc = parts[1].trim().to_string(); 
-            let command_text = parts[2].trim().to_string(); 
-
-            
+    for line_result in reader.lines() {
+        let line = line_result?;
+        let parts: Vec<&str> = line.splitn(4, '※').collect();
+        if parts.len() >= 3 {
+            let name = parts[0].trim().to_string();
+            let desc = parts[1].trim().to_string();
+            let command_text = parts[2].trim().to_string();
+            let shell = parts.get(3)
+                .map(|shell| shell.trim().to_string())
+                .filter(|shell| !shell.is_empty());
+
+
             let bash_cmd = BashCmd {
                 name,
                 desc,
                 command: command_text,
-                index: (commands.len() + 1) as u8, 
-                category: 1, 
-                favourite: false, 
+                favourite: false,
+                shell,
+                ..Default::default()
             };
-            commands.push(bash_cmd); 
+            commands.push(bash_cmd);
         }
-        
+
     }
     Ok(commands)
 }
 
-pub fn save_commands_to_file(app: &crate::app::App) -> io::Result<()> {
-    let path = get_commands_file_path();
-    let mut file = fs::File::create(path)?; 
-    for command in &app.items { 
-        writeln!(file, "{name}※{desc}※{command}", 
-                 name = command.name,
-                 desc = command.desc,
-                 command = command.command)?;
+// Resolves the command argument of `run`, `show`, `edit` and `rm`: an ID, else a name (exact
+// first, then ignoring case). Names are not unique, so an ambiguous one is an error.
+pub fn find_command_index(commands: &[BashCmd], key: &str) -> Result<usize, String> {
+    if let Ok(id) = key.parse::<u64>() {
+        if let Some(index) = commands.iter().position(|command| command.id == id) {
+            return Ok(index);
+        }
     }
-    Ok(())
+
+    let matchers: [&dyn Fn(&BashCmd) -> bool; 2] = [
+        &|command| command.name == key,
+        &|command| command.name.to_lowercase() == key.to_lowercase(),
+    ];
+    for matches in matchers {
+        let found: Vec<usize> = (0..commands.len()).filter(|&i| matches(&commands[i])).collect();
+        match found.as_slice() {
+            [] => continue,
+            [index] => return Ok(*index),
+            _ => {
+                let ids: Vec<String> = found.iter().map(|&i| commands[i].id.to_string()).collect();
+                return Err(format!("'{}' matches several commands (IDs {}), use the ID instead", key, ids.join(", ")));
+            }
+        }
+    }
+    Err(format!("no saved command named '{}'", key))
 }
 
-pub fn append_command_to_file(command: &BashCmd) -> io::Result<()> {
-    let path = get_commands_file_path();
-    let mut file = fs::OpenOptions::new()
-        .write(true)
-        .append(true)
-        .create(true)
-        .open(path)?;
-
-    writeln!(file, "{name}※{desc}※{command}", 
-                 name = command.name,
-                 desc = command.desc,
-                 command = command.command)?;
-
-    Ok(())
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(id: u64, name: &str) -> BashCmd {
+        BashCmd { id, name: name.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn finds_commands_by_id_then_name() {
+        let commands = vec![command(1, "deploy"), command(2, "Logs"), command(3, "logs"), command(4, "42"), command(5, "Tail")];
+        assert_eq!(find_command_index(&commands, "2"), Ok(1));
+        assert_eq!(find_command_index(&commands, "logs"), Ok(2));
+        assert_eq!(find_command_index(&commands, "42"), Ok(3));
+        assert_eq!(find_command_index(&commands, "tail"), Ok(4));
+        assert!(find_command_index(&[command(1, "a"), command(2, "a")], "a").is_err());
+        assert!(find_command_index(&commands, "missing").is_err());
+    }
+
+    #[test]
+    fn version_1_numeric_categories_load_as_uncategorized() {
+        let v1 = r#"
+            version = 1
+
+            [[commands]]
+            id = 1
+            name = "ls"
+            command = "ls -l"
+            category = 0
+
+            [[commands]]
+            name = "logs"
+            command = "kubectl logs web"
+            category = " k8s "
+        "#;
+        let store = parse_command_store(v1, Path::new("commands.toml")).unwrap();
+        assert_eq!(store.commands[0].category, "");
+        assert_eq!(store.commands[1].category, "k8s");
+        assert_eq!(store.commands[1].id, 2);
+        assert_eq!(store.categories, ["k8s"]);
+
+        let newer = format!("version = {}", STORE_VERSION + 1);
+        assert!(parse_command_store(&newer, Path::new("commands.toml")).is_err());
+    }
 }
@@ -0,0 +1,331 @@
+use clap::CommandFactory;
+
+use crate::cli::Cli;
+
+// Scripts printed by `bash-commander completions <shell>`. Subcommands and flags are taken from
+// the clap definitions when the script is generated; saved command names (for run/show/edit/rm/history),
+// the placeholders of a command (as `--param name=`) and profile names (for --profile) are looked up
+// at completion time through the hidden `__complete` subcommand, so they follow the store and the
+// config without regenerating the script.
+// Usage: source <(bash-commander completions bash), likewise for zsh;
+// bash-commander completions fish | source in config.fish.
+
+// Subcommands whose first positional argument is a saved command.
+const COMMAND_NAME_SUBCOMMANDS: [&str; 5] = ["run", "show", "edit", "rm", "history"];
+
+struct SubcommandSpec {
+    name: String,
+    about: String,
+    flags: Vec<String>,
+    value_flags: Vec<String>,
+}
+
+fn subcommand_specs() -> Vec<SubcommandSpec> {
+    let mut cli = Cli::command();
+    cli.build();
+    cli.get_subcommands()
+        .filter(|subcommand| !subcommand.is_hide_set() && subcommand.get_name() != "help")
+        .map(|subcommand| {
+            let mut flags = Vec::new();
+            let mut value_flags = Vec::new();
+            for arg in subcommand.get_arguments().filter(|arg| !arg.is_positional()) {
+                let names: Vec<String> = arg.get_long().map(|long| format!("--{}", long)).into_iter()
+                    .chain(arg.get_short().map(|short| format!("-{}", short)))
+                    .collect();
+                if arg.get_action().takes_values() {
+                    value_flags.extend(names.iter().cloned());
+                }
+                flags.extend(names);
+            }
+            SubcommandSpec {
+                name: subcommand.get_name().to_string(),
+                about: subcommand.get_about().map(|about| about.to_string()).unwrap_or_default(),
+                flags,
+                value_flags,
+            }
+        })
+        .collect()
+}
+
+// Options of the program itself, such as --profile, which may come before the subcommand.
+fn global_flags() -> Vec<String> {
+    let cli = Cli::command();
+    cli.get_arguments()
+        .filter(|arg| !arg.is_positional())
+        .flat_map(|arg| arg.get_long().map(|long| format!("--{}", long)).into_iter().chain(arg.get_short().map(|short| format!("-{}", short))))
+        .collect()
+}
+
+fn all_value_flags(specs: &[SubcommandSpec]) -> Vec<String> {
+    let mut flags: Vec<String> = specs.iter().flat_map(|spec| spec.value_flags.iter().cloned()).collect();
+    flags.sort();
+    flags.dedup();
+    flags
+}
+
+fn single_quoted(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn bash_script(specs: &[SubcommandSpec]) -> String {
+    let subcommands: Vec<&str> = specs.iter().map(|spec| spec.name.as_str()).collect();
+    let flag_cases: String = specs
+        .iter()
+        .map(|spec| format!("    {}) flags=\"{}\" ;;\n", spec.name, spec.flags.join(" ")))
+        .collect();
+
+    format!(r#"# Prints the N-th word that is neither a flag nor a flag's value: 1 is the subcommand, 2 the
+# saved command given to run/show/edit/rm/history. Global flags like --profile may come first.
+__bash_commander_positional() {{
+  local i word n=0 skip=0
+  for (( i = 1; i < COMP_CWORD; i++ )); do
+    word="${{COMP_WORDS[i]}}"
+    if (( skip )); then skip=0; continue; fi
+    case "$word" in
+      {value_flags}) skip=1 ;;
+      -*) ;;
+      *)
+        (( ++n == $1 )) || continue
+        # Undo the quoting typed on the command line, e.g. kube\ logs or 'kube logs'.
+        word="${{word#[\"\']}}"; word="${{word%[\"\']}}"
+        printf '%s' "${{word//\\/}}"
+        return 0
+        ;;
+    esac
+  done
+  return 1
+}}
+
+_bash_commander() {{
+  local cur="${{COMP_WORDS[COMP_CWORD]}}" prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+  local subcommand flags="" line command
+  COMPREPLY=()
+
+  if [[ "$prev" == --profile ]]; then
+    while IFS= read -r line; do
+      [[ "$line" == "$cur"* ]] && COMPREPLY+=("$line")
+    done < <(bash-commander __complete profiles 2>/dev/null)
+    return
+  fi
+
+  if ! subcommand="$(__bash_commander_positional 1)"; then
+    if [[ "$cur" == -* ]]; then
+      COMPREPLY=($(compgen -W "{global_flags}" -- "$cur"))
+    else
+      COMPREPLY=($(compgen -W "{subcommands}" -- "$cur"))
+    fi
+    return
+  fi
+
+  case "$subcommand" in
+{flag_cases}  esac
+
+  if [[ "$subcommand" == run && ( "$prev" == --param || "$prev" == -p ) ]]; then
+    command="$(__bash_commander_positional 2)" || return
+    compopt -o nospace 2>/dev/null
+    while IFS= read -r line; do
+      [[ "$line=" == "$cur"* ]] && COMPREPLY+=("$line=")
+    done < <(bash-commander __complete params "$command" 2>/dev/null)
+    return
+  fi
+
+  if [[ "$cur" == -* ]]; then
+    COMPREPLY=($(compgen -W "$flags" -- "$cur"))
+    return
+  fi
+
+  case "$subcommand" in
+    {name_subcommands})
+      if ! __bash_commander_positional 2 >/dev/null; then
+        while IFS= read -r line; do
+          [[ "$line" == "$cur"* ]] && COMPREPLY+=("$(printf '%q' "$line")")
+        done < <(bash-commander __complete names 2>/dev/null)
+      fi
+      ;;
+    completions|init)
+      COMPREPLY=($(compgen -W "bash zsh fish" -- "$cur"))
+      ;;
+    import|export)
+      COMPREPLY=($(compgen -f -- "$cur"))
+      ;;
+  esac
+}}
+complete -F _bash_commander bash-commander
+"#,
+        value_flags = all_value_flags(specs).join("|"),
+        global_flags = global_flags().join(" "),
+        subcommands = subcommands.join(" "),
+        flag_cases = flag_cases,
+        name_subcommands = COMMAND_NAME_SUBCOMMANDS.join("|"),
+    )
+}
+
+fn zsh_script(specs: &[SubcommandSpec]) -> String {
+    let described: String = specs
+        .iter()
+        .map(|spec| format!("    {}\n", single_quoted(&format!("{}:{}", spec.name, spec.about.replace(':', "\\:")))))
+        .collect();
+    let flag_cases: String = specs
+        .iter()
+        .map(|spec| format!("    {}) flags=({}) ;;\n", spec.name, spec.flags.join(" ")))
+        .collect();
+
+    format!(r#"#compdef bash-commander
+
+# Prints the N-th word that is neither a flag nor a flag's value: 1 is the subcommand, 2 the
+# saved command given to run/show/edit/rm/history. Global flags like --profile may come first.
+__bash_commander_positional() {{
+  local i n=0 skip=0
+  for (( i = 2; i < CURRENT; i++ )); do
+    if (( skip )); then skip=0; continue; fi
+    case "$words[i]" in
+      {value_flags}) skip=1 ;;
+      -*) ;;
+      *) (( ++n == $1 )) && {{ print -r -- "$words[i]"; return 0 }} ;;
+    esac
+  done
+  return 1
+}}
+
+_bash_commander() {{
+  local -a subcommands flags names params profiles
+  local subcommand command
+  subcommands=(
+{described}  )
+
+  if [[ "$words[CURRENT-1]" == --profile ]]; then
+    profiles=(${{(f)"$(bash-commander __complete profiles 2>/dev/null)"}})
+    compadd -a profiles
+    return
+  fi
+
+  if ! subcommand="$(__bash_commander_positional 1)"; then
+    if [[ "$PREFIX" == -* ]]; then
+      compadd -- {global_flags}
+    else
+      _describe 'subcommand' subcommands
+    fi
+    return
+  fi
+
+  case "$subcommand" in
+{flag_cases}  esac
+
+  if [[ "$subcommand" == run && ( "$words[CURRENT-1]" == --param || "$words[CURRENT-1]" == -p ) ]]; then
+    command="$(__bash_commander_positional 2)" || return
+    params=(${{(f)"$(bash-commander __complete params "$command" 2>/dev/null)"}})
+    compadd -S '' -- ${{^params}}=
+    return
+  fi
+
+  if [[ "$PREFIX" == -* ]]; then
+    compadd -- $flags
+    return
+  fi
+
+  case "$subcommand" in
+    {name_subcommands})
+      if ! __bash_commander_positional 2 >/dev/null; then
+        names=(${{(f)"$(bash-commander __complete names 2>/dev/null)"}})
+        compadd -a names
+      fi
+      ;;
+    completions|init) compadd bash zsh fish ;;
+    import|export) _files ;;
+  esac
+}}
+
+if [[ "$funcstack[1]" == _bash_commander ]]; then
+  _bash_commander "$@"
+else
+  compdef _bash_commander bash-commander
+fi
+"#,
+        value_flags = all_value_flags(specs).join("|"),
+        global_flags = global_flags().join(" "),
+        described = described,
+        flag_cases = flag_cases,
+        name_subcommands = COMMAND_NAME_SUBCOMMANDS.join("|"),
+    )
+}
+
+fn fish_script(specs: &[SubcommandSpec]) -> String {
+    let mut script = format!(r#"# Prints the N-th token that is neither a flag nor a flag's value: 1 is the subcommand, 2 the
+# saved command given to run/show/edit/rm/history. Global flags like --profile may come first.
+function __bash_commander_positional
+    set -l skip 0
+    set -l n 0
+    for token in (commandline -opc)[2..-1]
+        if test $skip = 1
+            set skip 0
+        else if contains -- $token {value_flags}
+            set skip 1
+        else if not string match -q -- '-*' $token
+            set n (math $n + 1)
+            if test $n = $argv[1]
+                echo $token
+                return 0
+            end
+        end
+    end
+    return 1
+end
+
+function __bash_commander_using
+    set -l subcommand (__bash_commander_positional 1); or return 1
+    contains -- $subcommand $argv
+end
+
+function __bash_commander_params
+    set -l command (__bash_commander_positional 2); or return
+    for param in (bash-commander __complete params $command 2>/dev/null)
+        echo $param=
+    end
+end
+
+complete -c bash-commander -f
+"#,
+        value_flags = all_value_flags(specs).join(" "),
+    );
+
+    for spec in specs {
+        script.push_str(&format!(
+            "complete -c bash-commander -n 'not __bash_commander_positional 1' -a {} -d {}\n",
+            spec.name, single_quoted(&spec.about),
+        ));
+        // run's --param and the global --profile get their own lines below with dynamic values.
+        let dynamic = |flag: &str| flag == "--profile" || (spec.name == "run" && (flag == "--param" || flag == "-p"));
+        for flag in spec.flags.iter().filter(|flag| !dynamic(flag)) {
+            let option = match flag.strip_prefix("--") {
+                Some(long) => format!("-l {}", long),
+                None => format!("-s {}", flag.trim_start_matches('-')),
+            };
+            let takes_value = if spec.value_flags.contains(flag) { " -r" } else { "" };
+            script.push_str(&format!(
+                "complete -c bash-commander -n '__bash_commander_using {}' {}{}\n",
+                spec.name, option, takes_value,
+            ));
+        }
+    }
+
+    script.push_str(&format!(
+        r#"complete -c bash-commander -n '__bash_commander_using {}; and not __bash_commander_positional 2' -a '(bash-commander __complete names 2>/dev/null)'
+complete -c bash-commander -n '__bash_commander_using run' -s p -l param -x -a '(__bash_commander_params)'
+complete -c bash-commander -l profile -x -a '(bash-commander __complete profiles 2>/dev/null)'
+complete -c bash-commander -n '__bash_commander_using completions init' -a 'bash zsh fish'
+complete -c bash-commander -n '__bash_commander_using import export' -F
+"#,
+        COMMAND_NAME_SUBCOMMANDS.join(" "),
+    ));
+    script
+}
+
+pub fn completion_script(shell: &str) -> Option<String> {
+    let specs = subcommand_specs();
+    match shell {
+        "bash" => Some(bash_script(&specs)),
+        "zsh" => Some(zsh_script(&specs)),
+        "fish" => Some(fish_script(&specs)),
+        _ => None,
+    }
+}
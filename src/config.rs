@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::app::{is_env_name, SortMode};
+use crate::commands::get_config_dir;
+use crate::danger::{DangerRule, DangerRules};
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Config {
+    // Shell used for every command that doesn't set its own; falls back to $SHELL, then bash.
+    pub shell: Option<String>,
+    // Run the shell interactively so the user's rc file (aliases, functions) is loaded.
+    pub source_rc: bool,
+    // Initial ordering of the command list; Ctrl+O cycles through the modes at runtime.
+    pub sort_mode: SortMode,
+    // Extra patterns that make a command ask for confirmation, on top of the built-in ones.
+    pub danger_rules: Vec<DangerRule>,
+    // The built-in rules plus `danger_rules`, compiled by `load_config`.
+    #[serde(skip)]
+    pub danger: DangerRules,
+    // Active environment profile at startup; the TUI switches it (Alt+P), the CLI takes --profile.
+    pub profile: Option<String>,
+    // Named variable sets, e.g. [profiles.prod], exported to every command while active.
+    pub profiles: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Config {
+    // Variables of the active profile; empty when none is active.
+    pub fn profile_vars(&self) -> BTreeMap<String, String> {
+        self.profile.as_ref().and_then(|name| self.profiles.get(name)).cloned().unwrap_or_default()
+    }
+
+    // `None` deactivates profiles.
+    pub fn set_profile(&mut self, name: Option<String>) -> Result<(), String> {
+        if let Some(name) = &name {
+            if !self.profiles.contains_key(name) {
+                return Err(format!("unknown profile '{}', known profiles: {}", name, self.profile_names().join(", ")));
+            }
+        }
+        self.profile = name;
+        Ok(())
+    }
+
+    pub fn profile_names(&self) -> Vec<String> {
+        self.profiles.keys().cloned().collect()
+    }
+}
+
+fn validate_profiles(config: &Config) -> Result<(), String> {
+    for (profile, vars) in &config.profiles {
+        if let Some(name) = vars.keys().find(|name| !is_env_name(name)) {
+            return Err(format!("invalid variable name '{}' in profile '{}'", name, profile));
+        }
+    }
+    match &config.profile {
+        Some(name) if !config.profiles.contains_key(name) => Err(format!("profile '{}' is not defined under [profiles]", name)),
+        _ => Ok(()),
+    }
+}
+
+pub fn get_config_file_path() -> PathBuf {
+    let mut path = get_config_dir();
+    path.push("config.toml");
+    path
+}
+
+pub fn load_config() -> io::Result<Config> {
+    let path = get_config_file_path();
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+
+    let contents = fs::read_to_string(&path)?;
+    let invalid = |err: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), err));
+    let mut config: Config = toml::from_str(&contents).map_err(|err| invalid(err.to_string()))?;
+    config.danger = DangerRules::compile(&config.danger_rules).map_err(invalid)?;
+    validate_profiles(&config).map_err(invalid)?;
+    Ok(config)
+}
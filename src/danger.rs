@@ -0,0 +1,113 @@
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::app::BashCmd;
+
+// Checks run on the command text (after template expansion) before it is executed. A match asks
+// for typed confirmation in the TUI and in `run`/`replay`; commands marked "always confirm" ask
+// every time. More rules can be added in config.toml:
+//   [[danger_rules]]
+//   pattern = '\bnpm\s+publish\b'
+//   reason = "publishes the package to the registry"
+
+#[derive(Deserialize, Clone)]
+pub struct DangerRule {
+    pub pattern: String,
+    pub reason: String,
+}
+
+// Patterns stop at `|`, `;` and `&` so a flag of a later command in a pipeline doesn't count.
+const BUILTIN_RULES: [(&str, &str); 12] = [
+    (r"\brm\b[^|;&\n]*\s(-[a-zA-Z]*[rR][a-zA-Z]*|--recursive)\b", "rm -r deletes whole directory trees"),
+    (r"\bgit\b[^|;&\n]*\bpush\b[^|;&\n]*\s(-f|--force|--force-with-lease|--mirror)\b", "git push --force rewrites the remote branch"),
+    (r"\bgit\b[^|;&\n]*\breset\b[^|;&\n]*\s--hard\b", "git reset --hard throws away uncommitted changes"),
+    (r"\bgit\b[^|;&\n]*\bclean\b[^|;&\n]*\s-[a-zA-Z]*f", "git clean -f deletes untracked files"),
+    (r"\bkubectl\b[^|;&\n]*\s(delete|drain)\b", "kubectl delete/drain removes cluster resources"),
+    (r"(?i)\b(drop\s+(table|database|schema)|truncate\s+(table\s+)?\w)", "DROP/TRUNCATE destroys database tables"),
+    (r#"(?i)\bdelete\s+from\s+[\w."`]+\s*($|;|'|")"#, "DELETE FROM without WHERE empties the table"),
+    (r"\bdd\b[^|;&\n]*\bof=/dev/", "dd writes directly to a device"),
+    (r"\bmkfs(\.\w+)?\b|>\s*/dev/(sd|nvme|hd|disk)", "overwrites a disk or file system"),
+    (r"\bch(mod|own)\b[^|;&\n]*\s-[a-zA-Z]*R[a-zA-Z]*\s+[^|;&\n]*\s/(\s|$)", "recursive permission change on /"),
+    (r"\b(shutdown|reboot|poweroff|halt)\b", "shuts down or restarts the machine"),
+    (r"\bterraform\b[^|;&\n]*\sdestroy\b", "terraform destroy tears down infrastructure"),
+];
+
+// Built-in and configured rules, compiled once when the config is loaded since the details pane
+// checks the selected command on every frame.
+#[derive(Clone)]
+pub struct DangerRules {
+    rules: Vec<(Regex, String)>,
+}
+
+impl DangerRules {
+    // A bad pattern is reported as an error of config.toml rather than silently ignored.
+    pub fn compile(configured: &[DangerRule]) -> Result<DangerRules, String> {
+        let builtin = BUILTIN_RULES.iter().map(|&(pattern, reason)| DangerRule { pattern: pattern.to_string(), reason: reason.to_string() });
+        let rules = builtin
+            .chain(configured.iter().cloned())
+            .map(|rule| match Regex::new(&rule.pattern) {
+                Ok(regex) => Ok((regex, rule.reason)),
+                Err(err) => Err(format!("invalid danger rule pattern '{}': {}", rule.pattern, err)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(DangerRules { rules })
+    }
+}
+
+impl Default for DangerRules {
+    fn default() -> DangerRules {
+        DangerRules::compile(&[]).expect("built-in danger rules are valid")
+    }
+}
+
+// Why the command needs confirmation; empty when it can run straight away.
+pub fn warnings(command: &BashCmd, rules: &DangerRules) -> Vec<String> {
+    let mut reasons: Vec<String> = rules.rules
+        .iter()
+        .filter(|(regex, _)| regex.is_match(&command.command))
+        .map(|(_, reason)| reason.clone())
+        .collect();
+    reasons.dedup();
+    if command.always_confirm {
+        reasons.push("marked as always confirm".to_string());
+    }
+    reasons
+}
+
+// What has to be typed to run a flagged command: its name, so it is read before it runs.
+pub fn confirmation_word(command: &BashCmd) -> String {
+    let name = command.name.trim();
+    if name.is_empty() { "yes".to_string() } else { name.to_string() }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flagged(text: &str) -> bool {
+        let command = BashCmd { command: text.to_string(), ..Default::default() };
+        !warnings(&command, &DangerRules::default()).is_empty()
+    }
+
+    #[test]
+    fn flags_destructive_commands_only() {
+        assert!(flagged("rm -rf build"));
+        assert!(flagged("sudo rm -v -R /tmp/cache"));
+        assert!(flagged("git push --force origin main"));
+        assert!(flagged("kubectl -n prod delete pod web-1"));
+        assert!(flagged("psql -c 'DROP TABLE users'"));
+        assert!(flagged("psql -c 'delete from users;'"));
+
+        assert!(!flagged("rm notes.txt"));
+        assert!(!flagged("git push origin main"));
+        assert!(!flagged("ls -R | grep rm -f"));
+        assert!(!flagged("kubectl get pods"));
+        assert!(!flagged("psql -c 'DELETE FROM users WHERE id = 3'"));
+
+        let rules = DangerRules::compile(&[DangerRule { pattern: r"\bnpm\s+publish\b".to_string(), reason: "publishes".to_string() }]).unwrap();
+        let command = BashCmd { command: "npm publish".to_string(), always_confirm: true, ..Default::default() };
+        assert_eq!(warnings(&command, &rules), vec!["publishes", "marked as always confirm"]);
+        assert!(DangerRules::compile(&[DangerRule { pattern: "(".to_string(), reason: String::new() }]).is_err());
+    }
+}
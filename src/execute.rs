@@ -1,24 +1,334 @@
+use std::collections::BTreeMap;
+use std::process::{Command, ExitStatus};
+use std::io::{Error, ErrorKind, Result};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
 
+use crate::app::BashCmd;
+use crate::config::Config;
 
-use std::process::{Command}; 
-use std::io::{Result}; 
-use std::os::unix::process::CommandExt;
 
+pub fn resolve_shell(cmd: &BashCmd, config: &Config) -> String {
+    let non_empty = |shell: &String| !shell.trim().is_empty();
+    cmd.shell.clone().filter(non_empty)
+        .or_else(|| config.shell.clone().filter(non_empty))
+        .or_else(|| env::var("SHELL").ok().filter(non_empty))
+        .unwrap_or_else(|| "bash".to_string())
+}
 
-pub fn execute_command(command_string_option: Option<String>) -> Result<()> { 
-    if let Some(command_to_execute) = command_string_option {
-        let parts: Vec<&str> = command_to_execute.split_whitespace().collect();
+fn shell_loads_rc_when_interactive(shell: &str) -> bool {
+    let shell_name = Path::new(shell)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(shell);
+    matches!(shell_name, "bash" | "zsh")
+}
 
-        if let Some(command_name) = parts.get(0) {
-            let mut command = Command::new(command_name);
+// Program and arguments that run the command; shared by `build_command` and the embedded terminal.
+// `source_rc` only applies when the command gets a terminal: an interactive shell without one
+// prints job-control warnings, and whatever the rc file prints would end up in captured output.
+pub fn shell_invocation(cmd: &BashCmd, config: &Config, has_terminal: bool) -> (String, Vec<String>) {
+    let shell = resolve_shell(cmd, config);
+    let mut args = Vec::new();
 
-            for arg in parts.iter().skip(1) {
-                command.arg(arg);
+    if has_terminal && config.source_rc && shell_loads_rc_when_interactive(&shell) {
+        args.push("-i".to_string());
+    }
+    // The stored text is handed over untouched so quoting, pipes and redirects survive.
+    args.push("-c".to_string());
+    args.push(cmd.command.clone());
+    (shell, args)
+}
+
+// `~` at the start and $NAME or ${NAME} anywhere, like a shell expands an assignment; `vars` are
+// looked up before the process environment. An unset variable is an error rather than an empty
+// string, so a typo can't send a command elsewhere.
+pub fn expand_vars(text: &str, vars: &BTreeMap<String, String>) -> Result<String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    if rest == "~" || rest.starts_with("~/") {
+        let home = dirs::home_dir().ok_or_else(|| Error::other("cannot expand ~, no home directory"))?;
+        expanded.push_str(&home.display().to_string());
+        rest = &rest[1..];
+    }
+    let is_name_char = |ch: char| ch.is_ascii_alphanumeric() || ch == '_';
+    while let Some(dollar) = rest.find('$') {
+        expanded.push_str(&rest[..dollar]);
+        let after = &rest[dollar + 1..];
+        let (name, remainder) = if let Some(braced) = after.strip_prefix('{') {
+            match braced.split_once('}') {
+                Some((name, remainder)) => (name, remainder),
+                None => return Err(Error::other(format!("unclosed ${{ in '{}'", text))),
             }
-            command.exec();
         } else {
+            let end = after.find(|ch: char| !is_name_char(ch)).unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+        if name.is_empty() {
+            expanded.push('$');
+        } else {
+            let value = vars.get(name).cloned().or_else(|| env::var(name).ok());
+            expanded.push_str(&value.ok_or_else(|| Error::other(format!("${} is not set", name)))?);
+        }
+        rest = remainder;
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+// The active profile's variables, themselves expanded against the process environment.
+fn profile_env(config: &Config) -> Result<BTreeMap<String, String>> {
+    let no_vars = BTreeMap::new();
+    config.profile_vars().into_iter().map(|(name, value)| Ok((name, expand_vars(&value, &no_vars)?))).collect()
+}
+
+pub fn resolve_workdir(cmd: &BashCmd, config: &Config) -> Result<Option<PathBuf>> {
+    let Some(workdir) = cmd.workdir.as_deref().map(str::trim).filter(|workdir| !workdir.is_empty()) else {
+        return Ok(None);
+    };
+    let dir = PathBuf::from(expand_vars(workdir, &profile_env(config)?)?);
+    if !dir.is_dir() {
+        return Err(Error::new(ErrorKind::NotFound, format!("working directory {} does not exist", dir.display())));
+    }
+    Ok(Some(dir))
+}
+
+// Profile variables plus the command's own, which win on a clash and may refer to the former.
+pub fn resolve_env(cmd: &BashCmd, config: &Config) -> Result<BTreeMap<String, String>> {
+    let mut vars = profile_env(config)?;
+    for (name, value) in &cmd.env {
+        let value = expand_vars(value, &vars)?;
+        vars.insert(name.clone(), value);
+    }
+    Ok(vars)
+}
+
+// Fails when the working directory or an environment value can't be resolved.
+pub fn build_command(cmd: &BashCmd, config: &Config, has_terminal: bool) -> Result<Command> {
+    let (shell, args) = shell_invocation(cmd, config, has_terminal);
+    let mut command = Command::new(shell);
+    command.args(args).envs(resolve_env(cmd, config)?);
+    if let Some(dir) = resolve_workdir(cmd, config)? {
+        command.current_dir(dir);
+    }
+    Ok(command)
+}
+
+// What running a command would do, read off the `Command` that would be spawned so the preview
+// can't drift from the real thing. Shown by `run --dry-run` and the preview popup (Alt+D).
+pub struct DryRun {
+    pub program: String,
+    pub args: Vec<String>,
+    pub cwd: Option<PathBuf>,
+    pub profile: Option<String>,
+    // Variables set (Some) or removed (None) for the command; everything else is inherited.
+    pub env: Vec<(String, Option<String>)>,
+}
+
+pub fn dry_run(cmd: &BashCmd, config: &Config) -> Result<DryRun> {
+    let command = build_command(cmd, config, true)?;
+    Ok(DryRun {
+        program: command.get_program().to_string_lossy().into_owned(),
+        args: command.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect(),
+        cwd: command.get_current_dir().map(Path::to_path_buf).or_else(|| env::current_dir().ok()),
+        profile: config.profile.clone(),
+        env: command
+            .get_envs()
+            .map(|(name, value)| (name.to_string_lossy().into_owned(), value.map(|value| value.to_string_lossy().into_owned())))
+            .collect(),
+    })
+}
+
+impl DryRun {
+    pub fn lines(&self) -> Vec<String> {
+        // The last argument is the command text itself, shown on its own below.
+        let (command, shell_args) = self.args.split_last().map_or(("", &[][..]), |(last, rest)| (last.as_str(), rest));
+        let mut lines = vec![
+            format!("Shell:       {} {}", self.program, shell_args.join(" ")),
+            format!("Directory:   {}", self.cwd.as_ref().map_or("?".to_string(), |cwd| cwd.display().to_string())),
+        ];
+        if let Some(profile) = &self.profile {
+            lines.push(format!("Profile:     {}", profile));
+        }
+        if self.env.is_empty() {
+            lines.push("Environment: inherited, no overrides".to_string());
+        }
+        for (index, (name, value)) in self.env.iter().enumerate() {
+            let label = if index == 0 { "Environment:" } else { "" };
+            match value {
+                Some(value) => lines.push(format!("{:<12} {}={}", label, name, value)),
+                None => lines.push(format!("{:<12} unset {}", label, name)),
+            }
+        }
+        lines.push("Command:".to_string());
+        lines.extend(command.lines().map(|line| format!("  {}", line)));
+        lines
+    }
+}
+
+// Replaces this process with the command. `before_exec` runs once the command could be built, so
+// nothing is recorded for a command whose directory or variables don't resolve.
+pub fn execute_command(command_option: Option<BashCmd>, config: &Config, before_exec: impl FnOnce()) -> Result<()> {
+    if let Some(command_to_execute) = command_option {
+        if command_to_execute.command.trim().is_empty() {
             eprintln!("No command provided");
+            return Ok(());
+        }
+        let mut command = build_command(&command_to_execute, config, true)?;
+        before_exec();
+        // exec only returns if the shell could not be started.
+        return Err(command.exec());
+    }
+    Ok(())
+}
+
+// The command as text for the user's own shell (`emit`). Without a working directory or
+// environment it is the command itself, ready to edit; otherwise it runs through its shell like
+// a run from the TUI would, e.g. `env NS=prod bash -c 'cd /srv || exit; kubectl get pods'`.
+pub fn shell_line(cmd: &BashCmd, config: &Config) -> Result<String> {
+    let workdir = resolve_workdir(cmd, config)?;
+    let env = resolve_env(cmd, config)?;
+    if workdir.is_none() && env.is_empty() {
+        return Ok(cmd.command.clone());
+    }
+
+    let (shell, mut args) = shell_invocation(cmd, config, true);
+    if let Some(dir) = workdir {
+        let script = args.pop().unwrap_or_default();
+        args.push(format!("cd {} || exit; {}", shell_quote(&dir.display().to_string()), script));
+    }
+    let mut words = Vec::new();
+    if !env.is_empty() {
+        words.push("env".to_string());
+        words.extend(env.iter().map(|(name, value)| format!("{}={}", name, shell_quote(value))));
+    }
+    words.push(shell_quote(&shell));
+    words.extend(args.iter().map(|arg| shell_quote(arg)));
+    Ok(words.join(" "))
+}
+
+// Runs the command as a child and waits for it, for callers that outlive the command (the CLI
+// `run`). Returns the exit code, with death by signal N reported as 128+N like a shell does.
+pub fn run_command(cmd: &BashCmd, config: &Config) -> Result<i32> {
+    let status = build_command(cmd, config, true)?.status()?;
+    Ok(exit_code_of(status))
+}
+
+pub fn exit_code_of(status: ExitStatus) -> i32 {
+    status.code().unwrap_or_else(|| 128 + status.signal().unwrap_or(0))
+}
+
+// A word the shell reads back as `text`: left bare when that is safe, otherwise in single quotes.
+pub fn shell_quote(text: &str) -> String {
+    let is_safe = |ch: char| ch.is_ascii_alphanumeric() || "_-.,:/@%+=".contains(ch);
+    if !text.is_empty() && text.chars().all(is_safe) {
+        return text.to_string();
+    }
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+// Splits `input` into words like a shell does for quoting: whitespace separates words, single
+// quotes keep everything literal, double quotes keep everything but `\"`, `\\`, `\$` and `\``
+// escapes, and a backslash outside quotes escapes the next character. Nothing is expanded.
+pub fn split_shell_words(input: &str) -> std::result::Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = input.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            ch if ch.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let current = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(ch) => current.push(ch),
+                        None => return Err("unclosed ' quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let current = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped @ ('"' | '\\' | '$' | '`')) => current.push(escaped),
+                            Some(other) => current.extend(['\\', other]),
+                            None => return Err("unclosed \" quote".to_string()),
+                        },
+                        Some(ch) => current.push(ch),
+                        None => return Err("unclosed \" quote".to_string()),
+                    }
+                }
+            }
+            '\\' => word.get_or_insert_with(String::new).extend(chars.next()),
+            ch => word.get_or_insert_with(String::new).push(ch),
         }
     }
-     Ok(())
-}
\ No newline at end of file
+    words.extend(word);
+    Ok(words)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dry_run_shows_the_invocation_that_would_run() {
+        let command = BashCmd { command: "echo one\necho two".to_string(), shell: Some("zsh".to_string()), ..Default::default() };
+        let dry_run = dry_run(&command, &Config::default()).unwrap();
+        assert_eq!(dry_run.program, "zsh");
+        assert_eq!(dry_run.args, vec!["-c", "echo one\necho two"]);
+        let lines = dry_run.lines();
+        assert_eq!(lines[0], "Shell:       zsh -c");
+        assert_eq!(&lines[lines.len() - 2..], ["  echo one", "  echo two"]);
+    }
+
+    #[test]
+    fn rc_file_is_only_sourced_with_a_terminal() {
+        let command = BashCmd { command: "ll".to_string(), shell: Some("bash".to_string()), ..Default::default() };
+        let config = Config { source_rc: true, ..Default::default() };
+        assert_eq!(shell_invocation(&command, &config, true).1, vec!["-i", "-c", "ll"]);
+        assert_eq!(shell_invocation(&command, &config, false).1, vec!["-c", "ll"]);
+    }
+
+    #[test]
+    fn expand_vars_fails_on_unset_variables() {
+        let path = env::var("PATH").unwrap();
+        assert_eq!(expand_vars("$PATH/bin:${PATH}", &BTreeMap::new()).unwrap(), format!("{}/bin:{}", path, path));
+        assert_eq!(expand_vars("costs $ 5", &BTreeMap::new()).unwrap(), "costs $ 5");
+        assert!(expand_vars("$BASH_COMMANDER_SURELY_UNSET/logs", &BTreeMap::new()).is_err());
+        assert!(expand_vars("${PATH", &BTreeMap::new()).is_err());
+    }
+
+    #[test]
+    fn command_env_overrides_and_refers_to_the_profile() {
+        let vars = |pairs: &[(&str, &str)]| -> BTreeMap<String, String> {
+            pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+        };
+        let prod = vars(&[("BC_TEST_NS", "prod"), ("REGION", "eu")]);
+        let mut config = Config { profiles: BTreeMap::from([("prod".to_string(), prod)]), ..Default::default() };
+        let command = BashCmd { env: vars(&[("REGION", "us"), ("TARGET", "${BC_TEST_NS}-db")]), ..Default::default() };
+
+        assert!(resolve_env(&command, &config).is_err());
+        config.set_profile(Some("prod".to_string())).unwrap();
+        assert_eq!(resolve_env(&command, &config).unwrap(), vars(&[("BC_TEST_NS", "prod"), ("REGION", "us"), ("TARGET", "prod-db")]));
+        assert!(config.set_profile(Some("staging".to_string())).is_err());
+    }
+
+    #[test]
+    fn shell_line_carries_workdir_and_env() {
+        let mut command = BashCmd { command: "ls -l | head".to_string(), shell: Some("bash".to_string()), ..Default::default() };
+        assert_eq!(shell_line(&command, &Config::default()).unwrap(), "ls -l | head");
+
+        command.workdir = Some("/".to_string());
+        command.env = BTreeMap::from([("GIT_SSH_COMMAND".to_string(), "ssh -i key".to_string())]);
+        assert_eq!(
+            shell_line(&command, &Config::default()).unwrap(),
+            "env GIT_SSH_COMMAND='ssh -i key' bash -c 'cd / || exit; ls -l | head'",
+        );
+    }
+}
@@ -0,0 +1,130 @@
+// fzf-style subsequence matching: every pattern character must appear in order, and matches
+// at word starts or directly after the previous match score higher than scattered ones.
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_BOUNDARY: i64 = 10;
+const BONUS_FIRST_CHAR: i64 = 4;
+const BONUS_CONSECUTIVE: i64 = 8;
+const PENALTY_GAP: i64 = 1;
+const BONUS_EXACT: i64 = 32;
+
+fn is_boundary(chars: &[char], i: usize) -> bool {
+    if i == 0 {
+        return true;
+    }
+    let prev = chars[i - 1];
+    let current = chars[i];
+    !prev.is_alphanumeric() || (prev.is_lowercase() && current.is_uppercase())
+}
+
+fn chars_equal(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+// Returns the score and the char positions of the best alignment, or None if `pattern` isn't a
+// subsequence of `text`. An empty pattern matches everything with score 0.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    if pattern.is_empty() {
+        return Some((0, Vec::new()));
+    }
+    if pattern.len() > text.len() {
+        return None;
+    }
+
+    // best[i][j]: best score with pattern[..=i] matched and pattern[i] placed on text[j].
+    let mut best: Vec<Vec<Option<i64>>> = vec![vec![None; text.len()]; pattern.len()];
+    let mut from: Vec<Vec<usize>> = vec![vec![0; text.len()]; pattern.len()];
+
+    for (i, &pattern_char) in pattern.iter().enumerate() {
+        // The best previous placement k that leaves a gap before j, kept as it runs along the row:
+        // its score plus the penalty already "paid back" up to k, so the gap to j is one subtraction.
+        let mut gap_best: Option<(i64, usize)> = None;
+        for j in i..text.len() {
+            if i > 0 && j > i {
+                let k = j - 2;
+                if let Some(previous) = best[i - 1][k] {
+                    let reach = previous + PENALTY_GAP * (k + 1) as i64;
+                    if gap_best.is_none_or(|(current, _)| reach > current) {
+                        gap_best = Some((reach, k));
+                    }
+                }
+            }
+            if !chars_equal(pattern_char, text[j]) {
+                continue;
+            }
+            let mut char_score = SCORE_MATCH;
+            if is_boundary(&text, j) {
+                char_score += BONUS_BOUNDARY;
+            }
+            if j == 0 {
+                char_score += BONUS_FIRST_CHAR;
+            }
+
+            if i == 0 {
+                best[i][j] = Some(char_score);
+                continue;
+            }
+            if let Some((reach, k)) = gap_best {
+                best[i][j] = Some(reach - PENALTY_GAP * j as i64 + char_score);
+                from[i][j] = k;
+            }
+            if let Some(previous) = best[i - 1][j - 1] {
+                let candidate = previous + BONUS_CONSECUTIVE + char_score;
+                if best[i][j].is_none_or(|current| candidate > current) {
+                    best[i][j] = Some(candidate);
+                    from[i][j] = j - 1;
+                }
+            }
+        }
+    }
+
+    let last = pattern.len() - 1;
+    let (mut j, mut score) = best[last]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, score)| score.map(|score| (j, score)))
+        .max_by_key(|&(j, score)| (score, std::cmp::Reverse(j)))?;
+
+    let mut positions = vec![0; pattern.len()];
+    for i in (0..pattern.len()).rev() {
+        positions[i] = j;
+        if i > 0 {
+            j = from[i][j];
+        }
+    }
+    if pattern.len() == text.len() {
+        score += BONUS_EXACT;
+    }
+    Some((score, positions))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_word_initials() {
+        let (_, positions) = fuzzy_match("dcl", "docker compose logs").unwrap();
+        assert_eq!(positions, vec![0, 7, 15]);
+        assert!(fuzzy_match("dlc", "docker compose logs").is_none());
+    }
+
+    #[test]
+    fn prefers_consecutive_and_boundary_matches() {
+        let (tight, _) = fuzzy_match("log", "docker compose logs").unwrap();
+        let (scattered, _) = fuzzy_match("log", "lorem ipsum gag").unwrap();
+        assert!(tight > scattered);
+    }
+
+    #[test]
+    fn gaps_cost_their_length() {
+        let (_, positions) = fuzzy_match("ab", "a--a-b").unwrap();
+        assert_eq!(positions, vec![0, 5]);
+        let (near, _) = fuzzy_match("ab", "xa-b").unwrap();
+        let (far, _) = fuzzy_match("ab", "xa---b").unwrap();
+        assert_eq!(near - far, 2 * PENALTY_GAP);
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::BashCmd;
+use crate::commands::get_config_dir;
+use crate::config::Config;
+use crate::execute;
+use crate::template;
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+
+// Where a run was started: Enter in the TUI (or the shell widget), the CLI, the output pane or
+// the embedded terminal.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Invocation {
+    Tui,
+    Cli,
+    Inside,
+    Terminal,
+}
+
+impl Invocation {
+    pub fn label(self) -> &'static str {
+        match self {
+            Invocation::Tui => "tui",
+            Invocation::Cli => "cli",
+            Invocation::Inside => "inside",
+            Invocation::Terminal => "terminal",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct HistoryEntry {
+    pub command_id: u64,
+    // When the run started; frecency is computed from it.
+    pub timestamp: u64,
+    // Unknown when the command replaced this process (exec) or was put on the prompt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    // Entries written by older versions only have the fields above.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    // The command text as run, with its placeholders filled in.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<Invocation>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, String>,
+    // Environment profile active during the run; a replay uses it again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+}
+
+impl HistoryEntry {
+    // A run of `command` (already expanded) starting now, in its working directory or the current one.
+    pub fn start(command: &BashCmd, mode: Invocation, params: BTreeMap<String, String>, config: &Config) -> HistoryEntry {
+        HistoryEntry {
+            command_id: command.id,
+            timestamp: now(),
+            exit_code: None,
+            name: command.name.clone(),
+            command: command.command.clone(),
+            cwd: execute::resolve_workdir(command, config).ok().flatten().or_else(|| env::current_dir().ok()).map(|dir| dir.display().to_string()),
+            finished: None,
+            mode: Some(mode),
+            params,
+            profile: config.profile.clone(),
+        }
+    }
+
+    pub fn finish(mut self, exit_code: Option<i32>) -> HistoryEntry {
+        self.exit_code = exit_code;
+        self.finished = Some(now());
+        self
+    }
+}
+
+pub fn get_history_file_path() -> PathBuf {
+    let mut path = get_config_dir();
+    path.push("history.jsonl");
+    path
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+// One JSON object per line, so recording a run never rewrites the whole file.
+pub fn record_execution(entry: &HistoryEntry) -> io::Result<()> {
+    let line = serde_json::to_string(entry)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let mut file = fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(get_history_file_path())?;
+    writeln!(file, "{}", line)
+}
+
+pub fn load_history() -> io::Result<Vec<HistoryEntry>> {
+    let path = get_history_file_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let reader = BufReader::new(fs::File::open(path)?);
+    let mut entries = Vec::new();
+    for line_result in reader.lines() {
+        let line = line_result?;
+        // A line cut short by a crash shouldn't cost the rest of the history.
+        if let Ok(entry) = serde_json::from_str(&line) {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+// History entries are numbered from 1 in the order they were recorded, like shell history.
+pub fn entry_by_number(entries: &[HistoryEntry], number: usize) -> Result<&HistoryEntry, String> {
+    number
+        .checked_sub(1)
+        .and_then(|index| entries.get(index))
+        .ok_or_else(|| format!("no history entry {}, there are {}", number, entries.len()))
+}
+
+// Entries of old versions carry only the command ID; they are shown with the current name.
+pub fn fill_missing_names(entries: &mut [HistoryEntry], commands: &[BashCmd]) {
+    for entry in entries.iter_mut().filter(|entry| entry.name.is_empty()) {
+        if let Some(command) = commands.iter().find(|command| command.id == entry.command_id) {
+            entry.name = command.name.clone();
+        }
+    }
+}
+
+// The command to run for a replay: the recorded text (so the same parameter values are used
+// even if the template changed since), with the shell of the saved command when it still exists.
+// Entries from before the text was recorded can only be replayed if the command has no placeholders.
+pub fn replay_command(entry: &HistoryEntry, commands: &[BashCmd]) -> Result<BashCmd, String> {
+    let saved = commands.iter().find(|command| command.id == entry.command_id);
+    let text = if !entry.command.is_empty() {
+        entry.command.clone()
+    } else {
+        match saved {
+            Some(command) if !template::has_placeholders(&command.command) => template::unescape(&command.command),
+            Some(command) => return Err(format!("the run of '{}' was recorded without its parameter values", command.name)),
+            None => return Err(format!("command {} no longer exists", entry.command_id)),
+        }
+    };
+    let mut command = saved.cloned().unwrap_or_else(|| BashCmd {
+        id: entry.command_id,
+        name: entry.name.clone(),
+        ..Default::default()
+    });
+    command.command = text;
+    Ok(command)
+}
+
+// Local time as `YYYY-MM-DD HH:MM`.
+pub fn format_timestamp(timestamp: u64) -> String {
+    let time = timestamp as libc::time_t;
+    // SAFETY: `tm` is plain integers (and a pointer that may be null), so all zeroes is a valid
+    // value; localtime_r only reads `time` and writes the struct it is given.
+    let converted = unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        (!libc::localtime_r(&time, &mut tm).is_null()).then_some(tm)
+    };
+    let Some(tm) = converted else {
+        return timestamp.to_string();
+    };
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", tm.tm_year + 1900, tm.tm_mon + 1, tm.tm_mday, tm.tm_hour, tm.tm_min)
+}
+
+// Each run adds a weight that decays with its age, so the score grows with how often a
+// command is used and fades when it hasn't been used for a while.
+fn recency_weight(age: u64) -> f64 {
+    match age {
+        age if age < 4 * HOUR => 100.0,
+        age if age < DAY => 80.0,
+        age if age < 7 * DAY => 60.0,
+        age if age < 30 * DAY => 40.0,
+        age if age < 90 * DAY => 20.0,
+        _ => 10.0,
+    }
+}
+
+pub fn frecency_scores(entries: &[HistoryEntry], now: u64) -> HashMap<u64, f64> {
+    let mut scores = HashMap::new();
+    for entry in entries {
+        let age = now.saturating_sub(entry.timestamp);
+        *scores.entry(entry.command_id).or_insert(0.0) += recency_weight(age);
+    }
+    scores
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replays_the_recorded_text_of_old_and_new_entries() {
+        let saved = vec![
+            BashCmd { id: 1, name: "logs".to_string(), command: "kubectl logs {{pod}}".to_string(), shell: Some("zsh".to_string()), ..Default::default() },
+            BashCmd { id: 2, name: "ls".to_string(), command: "ls -l".to_string(), ..Default::default() },
+        ];
+        let old: HistoryEntry = serde_json::from_str(r#"{"command_id":2,"timestamp":10}"#).unwrap();
+        assert_eq!(replay_command(&old, &saved).unwrap().command, "ls -l");
+
+        let old_template: HistoryEntry = serde_json::from_str(r#"{"command_id":1,"timestamp":10,"exit_code":0}"#).unwrap();
+        assert!(replay_command(&old_template, &saved).is_err());
+
+        let entry = HistoryEntry { command_id: 1, command: "kubectl logs web-1".to_string(), ..Default::default() };
+        let command = replay_command(&entry, &saved).unwrap();
+        assert_eq!(command.command, "kubectl logs web-1");
+        assert_eq!(command.shell.as_deref(), Some("zsh"));
+
+        assert!(entry_by_number(&[entry], 0).is_err());
+    }
+
+    #[test]
+    fn frecency_adds_decaying_weights_per_command() {
+        let now = 200 * DAY;
+        let run = |command_id: u64, age: u64| HistoryEntry { command_id, timestamp: now - age, name: "run".to_string(), ..Default::default() };
+        let mut entries = vec![
+            run(1, HOUR),
+            run(2, 2 * DAY),
+            run(2, 10 * DAY),
+            run(2, 100 * DAY),
+            // A command that no longer exists still gets a score; nothing looks it up.
+            run(99, 5 * DAY),
+        ];
+        // Old entries have no name, and a clock that went backwards counts as a fresh run.
+        entries.push(serde_json::from_str(&format!(r#"{{"command_id":3,"timestamp":{}}}"#, now - 40 * DAY)).unwrap());
+        entries.push(serde_json::from_str(&format!(r#"{{"command_id":3,"timestamp":{}}}"#, now + HOUR)).unwrap());
+
+        let scores = frecency_scores(&entries, now);
+        assert_eq!(scores[&1], 100.0);
+        assert_eq!(scores[&2], 60.0 + 40.0 + 10.0);
+        assert_eq!(scores[&3], 20.0 + 100.0);
+        assert_eq!(scores[&99], 60.0);
+        assert!(!scores.contains_key(&4));
+
+        // One recent run outranks a single old one, but frequent older use outranks both.
+        assert!(scores[&1] > recency_weight(100 * DAY));
+        assert!(scores[&2] > scores[&1]);
+    }
+}
@@ -3,6 +3,18 @@ mod ui;
 mod commands;
 mod execute;
 mod cli;
+mod config;
+mod template;
+mod fuzzy;
+mod query;
+mod history;
+mod shell_integration;
+mod shell_history;
+mod output;
+mod completions;
+mod runner;
+mod pty;
+mod danger;
 
 use ratatui:: { backend::CrosstermBackend, Terminal};
 use crossterm::{
@@ -13,21 +25,30 @@ use crossterm::{
 use std::{
     env,
     error::Error,
-    io::{self},
-    io::Write
+    fs,
+    io::{self, Write},
+    mem,
+    path::PathBuf,
+    process,
 };
 
-use crate::cli::text_flow;
+use clap::Parser;
 
-fn default_flow() -> Result<(), Box<dyn Error>> {
+use crate::app::BashCmd;
+use crate::cli::{Cli, CliCommand};
+
+// Runs `run` with the terminal switched to raw mode on the alternate screen of `out`, restoring
+// it afterwards even when `run` fails.
+fn run_in_terminal<W: Write, T>(
+    mut out: W,
+    run: impl FnOnce(&mut Terminal<CrosstermBackend<W>>) -> io::Result<T>,
+) -> Result<io::Result<T>, Box<dyn Error>> {
     enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
+    execute!(out, EnterAlternateScreen, EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(out);
     let mut terminal = Terminal::new(backend)?;
 
-    let app = app::App::new();
-    let res = ui::run_app(&mut terminal, app);
+    let res = run(&mut terminal);
 
     disable_raw_mode()?;
     execute!(
@@ -36,28 +57,159 @@ fn default_flow() -> Result<(), Box<dyn Error>> {
         DisableMouseCapture
     )?;
     terminal.show_cursor()?;
+    Ok(res)
+}
+
+// `--profile` wins over the profile set in config.toml. A config that doesn't load is an error
+// rather than a fallback to the defaults, which would drop the user's danger rules.
+fn load_config_with_profile(profile: Option<String>) -> Result<config::Config, Box<dyn Error>> {
+    let mut config = config::load_config()?;
+    if profile.is_some() {
+        config.set_profile(profile)?;
+    }
+    Ok(config)
+}
+
+fn record_history(entry: &history::HistoryEntry) {
+    if let Err(err) = history::record_execution(entry) {
+        eprintln!("Failed to record history: {}", err);
+    }
+}
+
+// Runs the TUI on `out` and returns the chosen command with its template already expanded, and
+// the history entry to record once it is used. The profile switched to in the TUI (or the one of
+// a replayed run) is written back to `config` for running the command.
+fn select_command<W: Write>(config: &mut config::Config, out: W) -> Result<Option<(BashCmd, history::HistoryEntry)>, Box<dyn Error>> {
+    // Fail before touching the terminal so a broken store is reported instead of overwritten on exit.
+    let command_store = commands::load_command_store()?;
+    let history_entries = history::load_history().unwrap_or_default();
+
+    let mut app = app::App::new(config.clone(), command_store);
+    app.set_frecency(history::frecency_scores(&history_entries, history::now()));
+    let res = run_in_terminal(out, |terminal| ui::run_app(terminal, &mut app))?;
 
-    let selected_command_string_option = match res { 
-        Ok(command_string_option) => command_string_option, 
-        Err(err) => {
-            println!("{:?}", err);
-            None 
+    let Some(command) = res? else {
+        return Ok(None);
+    };
+    // A replay runs where the original run did, if that directory is still there.
+    if let Some(cwd) = app.replay_entry.as_ref().and_then(|entry| entry.cwd.as_ref()) {
+        if let Err(err) = env::set_current_dir(cwd) {
+            eprintln!("Cannot enter {}: {}", cwd, err);
         }
+    }
+    config.profile = app.run_config().profile;
+    // Emitted and exec'd commands both leave this process, so their exit status is never known.
+    let entry = history::HistoryEntry::start(&command, history::Invocation::Tui, mem::take(&mut app.chosen_params), config);
+    Ok(Some((command, entry)))
+}
+
+fn default_flow(profile: Option<String>) -> Result<(), Box<dyn Error>> {
+    let mut config = load_config_with_profile(profile)?;
+    let Some((command, entry)) = select_command(&mut config, io::stdout())? else {
+        return Ok(());
+    };
+    execute::execute_command(Some(command), &config, || record_history(&entry))?;
+    Ok(())
+}
+
+// `emit`: print the chosen command instead of running it, so shell widgets can put it on the
+// prompt. The TUI draws on stderr to keep stdout clean for capture.
+fn emit_flow(fd: Option<u32>, output: Option<PathBuf>, profile: Option<String>) -> Result<(), Box<dyn Error>> {
+    let output_path = fd.map(|fd| PathBuf::from(format!("/dev/fd/{}", fd))).or(output);
+
+    let mut config = load_config_with_profile(profile)?;
+    let Some((command, entry)) = select_command(&mut config, io::stderr())? else {
+        return Ok(());
     };
+    // The working directory, environment and profile go along, so the prompt runs what Enter would.
+    let line = execute::shell_line(&command, &config)?;
+    record_history(&entry);
+    match output_path {
+        Some(path) => fs::OpenOptions::new().write(true).create(true).truncate(true).open(path)?
+            .write_all(line.as_bytes())?,
+        None => io::stdout().write_all(line.as_bytes())?,
+    }
+    Ok(())
+}
+
+fn init_flow(shell: &str) -> Result<(), Box<dyn Error>> {
+    match shell_integration::init_script(shell) {
+        Some(script) => {
+            print!("{}", script);
+            Ok(())
+        }
+        None => Err(format!("unsupported shell '{}', expected one of: bash, zsh, fish", shell).into()),
+    }
+}
+
+fn completions_flow(shell: &str) -> Result<(), Box<dyn Error>> {
+    match completions::completion_script(shell) {
+        Some(script) => {
+            print!("{}", script);
+            Ok(())
+        }
+        None => Err(format!("unsupported shell '{}', expected one of: bash, zsh, fish", shell).into()),
+    }
+}
 
-    execute::execute_command(selected_command_string_option); 
-        
+// `import-history`: pick lines from the bash/zsh/fish history files and save them as commands.
+fn import_history_flow() -> Result<(), Box<dyn Error>> {
+    let command_store = commands::load_command_store()?;
+    let candidates = shell_history::load_candidates(&command_store.commands)?;
+    if candidates.is_empty() {
+        println!("No new commands found in shell history.");
+        return Ok(());
+    }
+
+    let picker = app::ImportPicker::new(candidates);
+    let chosen = run_in_terminal(io::stdout(), |terminal| ui::run_import_picker(terminal, picker))??;
+    if chosen.is_empty() {
+        println!("Nothing imported.");
+        return Ok(());
+    }
+
+    let imported: Vec<BashCmd> = chosen.iter().map(|command| shell_history::imported_command(command)).collect();
+    commands::append_commands_to_file(&imported)?;
+    println!("Imported {} commands.", imported.len());
     Ok(())
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-   let args: Vec<String> = env::args().collect();
+fn main() {
+    // Errors are printed as their message; returning them from `main` would print the debug form.
+    if let Err(err) = run(Cli::parse()) {
+        eprintln!("bash-commander: {}", err);
+        process::exit(1);
+    }
+}
 
-    if args.len() > 1 {
-        text_flow();
-    } else {
-        default_flow();
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    match cli.command {
+        None | Some(CliCommand::Tui) => default_flow(cli.profile)?,
+        Some(CliCommand::Add(args)) => cli::add_flow(args)?,
+        Some(CliCommand::List { category, tag, format }) => cli::list_flow(category, tag, format)?,
+        Some(CliCommand::Search { query, format }) => cli::search_flow(&query, format)?,
+        Some(CliCommand::Run { command, params, yes, dry_run }) => {
+            let exit_code = cli::run_flow(&command, &params, yes, dry_run, &load_config_with_profile(cli.profile)?)?;
+            process::exit(exit_code);
+        }
+        Some(CliCommand::Show { command, format }) => cli::show_flow(&command, format)?,
+        Some(CliCommand::History { command, limit, format }) => cli::history_flow(command, limit, format)?,
+        Some(CliCommand::Replay { number, yes, dry_run }) => {
+            let exit_code = cli::replay_flow(number, cli.profile, yes, dry_run, config::load_config()?)?;
+            process::exit(exit_code);
+        }
+        Some(CliCommand::Edit(args)) => cli::edit_flow(args)?,
+        Some(CliCommand::Rm { command, yes }) => cli::rm_flow(&command, yes)?,
+        Some(CliCommand::Import { path }) => cli::import_flow(path)?,
+        Some(CliCommand::Export { output }) => cli::export_flow(output)?,
+        Some(CliCommand::Emit { fd, output }) => emit_flow(fd, output, cli.profile)?,
+        Some(CliCommand::Init { shell }) => init_flow(&shell)?,
+        Some(CliCommand::SaveLast { command }) => cli::save_last_flow(command),
+        Some(CliCommand::ImportHistory) => import_history_flow()?,
+        Some(CliCommand::Completions { shell }) => completions_flow(&shell)?,
+        Some(CliCommand::Complete { target, command }) => cli::complete_flow(target, command),
+        Some(CliCommand::Save(args)) => cli::text_flow(&args),
     }
 
-   Ok(())
-}
\ No newline at end of file
+    Ok(())
+}
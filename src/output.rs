@@ -0,0 +1,280 @@
+use std::collections::BTreeMap;
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::app::{format_env, BashCmd};
+use crate::history::{format_timestamp, HistoryEntry, Invocation};
+
+// Output of `list`, `search`, `show` and `history`. The JSON and TSV layouts are a public interface
+// documented in docs/output-format.md: add fields at the end, never rename or drop them.
+
+#[derive(ValueEnum, Clone, Copy, Default)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Tsv,
+}
+
+#[derive(Serialize)]
+struct CommandRecord<'a> {
+    id: u64,
+    name: &'a str,
+    description: &'a str,
+    command: &'a str,
+    category: Option<&'a str>,
+    tags: &'a [String],
+    favourite: bool,
+    shell: Option<&'a str>,
+    notes: &'a str,
+    metadata: &'a BTreeMap<String, String>,
+    always_confirm: bool,
+    workdir: Option<&'a str>,
+    env: &'a BTreeMap<String, String>,
+}
+
+impl<'a> CommandRecord<'a> {
+    fn new(command: &'a BashCmd) -> CommandRecord<'a> {
+        CommandRecord {
+            id: command.id,
+            name: &command.name,
+            description: &command.desc,
+            command: &command.command,
+            category: Some(command.category.as_str()).filter(|category| !category.is_empty()),
+            tags: &command.tags,
+            favourite: command.favourite,
+            shell: command.shell.as_deref(),
+            notes: &command.notes,
+            metadata: &command.metadata,
+            always_confirm: command.always_confirm,
+            workdir: command.workdir.as_deref(),
+            env: &command.env,
+        }
+    }
+}
+
+const TSV_HEADER: [&str; 13] = [
+    "id", "name", "description", "command", "category", "tags", "favourite", "shell", "notes", "metadata", "always_confirm", "workdir", "env",
+];
+
+fn escape_tsv(field: &str) -> String {
+    field.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+// Maps go into a TSV cell as a JSON object, or stay empty.
+fn json_cell(map: &BTreeMap<String, String>) -> String {
+    if map.is_empty() { String::new() } else { serde_json::to_string(map).unwrap_or_default() }
+}
+
+fn tsv_row(command: &BashCmd) -> String {
+    let fields = [
+        command.id.to_string(),
+        command.name.clone(),
+        command.desc.clone(),
+        command.command.clone(),
+        command.category.clone(),
+        command.tags.join(","),
+        command.favourite.to_string(),
+        command.shell.clone().unwrap_or_default(),
+        command.notes.clone(),
+        json_cell(&command.metadata),
+        command.always_confirm.to_string(),
+        command.workdir.clone().unwrap_or_default(),
+        json_cell(&command.env),
+    ];
+    fields.iter().map(|field| escape_tsv(field)).collect::<Vec<_>>().join("\t")
+}
+
+fn first_line(text: &str) -> &str {
+    text.lines().next().unwrap_or("")
+}
+
+fn table_rows(commands: &[&BashCmd]) -> String {
+    let name_width = commands.iter().map(|command| command.name.chars().count()).max().unwrap_or(0);
+    commands
+        .iter()
+        .map(|command| {
+            let marker = if command.favourite { "★" } else { " " };
+            format!("{:>4} {} {:<width$}  {}\n", command.id, marker, command.name, first_line(&command.command), width = name_width)
+        })
+        .collect()
+}
+
+fn table_details(command: &BashCmd) -> String {
+    let mut lines = vec![
+        format!("ID: {}", command.id),
+        format!("Name: {}", command.name),
+        format!("Description: {}", command.desc),
+        format!("Category: {}", command.category),
+        format!("Tags: {}", command.tags.join(", ")),
+        format!("Favourite: {}", if command.favourite { "yes" } else { "no" }),
+    ];
+    if command.always_confirm {
+        lines.push("Always confirm: yes".to_string());
+    }
+    if let Some(shell) = &command.shell {
+        lines.push(format!("Shell: {}", shell));
+    }
+    if let Some(workdir) = &command.workdir {
+        lines.push(format!("Directory: {}", workdir));
+    }
+    if !command.env.is_empty() {
+        lines.push(format!("Env: {}", format_env(&command.env)));
+    }
+    if !command.notes.is_empty() {
+        lines.push(format!("Notes: {}", command.notes));
+    }
+    for (key, value) in &command.metadata {
+        lines.push(format!("{}: {}", key, value));
+    }
+    lines.push(format!("Command:\n{}", command.command));
+    lines.join("\n") + "\n"
+}
+
+// `list` and `search`: a JSON array, or one TSV row per command after a header row.
+pub fn format_commands(commands: &[&BashCmd], format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Table => table_rows(commands),
+        OutputFormat::Json => {
+            let records: Vec<CommandRecord> = commands.iter().map(|command| CommandRecord::new(command)).collect();
+            serde_json::to_string_pretty(&records).unwrap_or_default() + "\n"
+        }
+        OutputFormat::Tsv => {
+            let mut output = TSV_HEADER.join("\t") + "\n";
+            for command in commands {
+                output.push_str(&tsv_row(command));
+                output.push('\n');
+            }
+            output
+        }
+    }
+}
+
+// `show`: a single JSON object, or a header row plus one TSV row.
+pub fn format_command(command: &BashCmd, format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Table => table_details(command),
+        OutputFormat::Json => serde_json::to_string_pretty(&CommandRecord::new(command)).unwrap_or_default() + "\n",
+        OutputFormat::Tsv => format_commands(&[command], OutputFormat::Tsv),
+    }
+}
+
+#[derive(Serialize)]
+struct HistoryRecord<'a> {
+    number: usize,
+    command_id: u64,
+    name: &'a str,
+    command: &'a str,
+    cwd: Option<&'a str>,
+    started: u64,
+    finished: Option<u64>,
+    exit_code: Option<i32>,
+    mode: Option<Invocation>,
+    params: &'a BTreeMap<String, String>,
+    profile: Option<&'a str>,
+}
+
+const HISTORY_TSV_HEADER: [&str; 11] = [
+    "number", "command_id", "name", "command", "cwd", "started", "finished", "exit_code", "mode", "params", "profile",
+];
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+fn history_tsv_row(number: usize, entry: &HistoryEntry) -> String {
+    let fields = [
+        number.to_string(),
+        entry.command_id.to_string(),
+        entry.name.clone(),
+        entry.command.clone(),
+        entry.cwd.clone().unwrap_or_default(),
+        entry.timestamp.to_string(),
+        optional(entry.finished),
+        optional(entry.exit_code),
+        optional(entry.mode.map(Invocation::label)),
+        json_cell(&entry.params),
+        entry.profile.clone().unwrap_or_default(),
+    ];
+    fields.iter().map(|field| escape_tsv(field)).collect::<Vec<_>>().join("\t")
+}
+
+// Status column of the history table: the exit code, or `-` when it isn't known.
+fn exit_status(entry: &HistoryEntry) -> String {
+    entry.exit_code.map_or_else(|| "-".to_string(), |code| code.to_string())
+}
+
+fn history_table(entries: &[(usize, &HistoryEntry)]) -> String {
+    entries
+        .iter()
+        .map(|(number, entry)| {
+            let mode = entry.mode.map_or("", Invocation::label);
+            let command = if entry.command.is_empty() { &entry.name } else { &entry.command };
+            format!("{:>5}  {}  {:>4}  {:<8}  {}\n", number, format_timestamp(entry.timestamp), exit_status(entry), mode, first_line(command))
+        })
+        .collect()
+}
+
+// `history`: entries with their numbers, oldest first, as a JSON array or TSV rows.
+pub fn format_history(entries: &[(usize, &HistoryEntry)], format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Table => history_table(entries),
+        OutputFormat::Json => {
+            let records: Vec<HistoryRecord> = entries
+                .iter()
+                .map(|&(number, entry)| HistoryRecord {
+                    number,
+                    command_id: entry.command_id,
+                    name: &entry.name,
+                    command: &entry.command,
+                    cwd: entry.cwd.as_deref(),
+                    started: entry.timestamp,
+                    finished: entry.finished,
+                    exit_code: entry.exit_code,
+                    mode: entry.mode,
+                    params: &entry.params,
+                    profile: entry.profile.as_deref(),
+                })
+                .collect();
+            serde_json::to_string_pretty(&records).unwrap_or_default() + "\n"
+        }
+        OutputFormat::Tsv => {
+            let mut output = HISTORY_TSV_HEADER.join("\t") + "\n";
+            for (number, entry) in entries {
+                output.push_str(&history_tsv_row(*number, entry));
+                output.push('\n');
+            }
+            output
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_and_tsv_use_the_documented_layout() {
+        let command = BashCmd {
+            id: 7,
+            name: "loop".to_string(),
+            desc: "tab\there".to_string(),
+            command: "for f in *; do\n  echo \"$f\"\ndone".to_string(),
+            tags: vec!["shell".to_string(), "demo".to_string()],
+            ..Default::default()
+        };
+
+        let json: serde_json::Value = serde_json::from_str(&format_command(&command, OutputFormat::Json)).unwrap();
+        assert_eq!(json["description"], "tab\there");
+        assert_eq!(json["category"], serde_json::Value::Null);
+        assert_eq!(json["tags"], serde_json::json!(["shell", "demo"]));
+        assert_eq!(json["env"], serde_json::json!({}));
+
+        let tsv = format_command(&command, OutputFormat::Tsv);
+        let rows: Vec<&str> = tsv.lines().collect();
+        assert_eq!(rows[0].split('\t').count(), TSV_HEADER.len());
+        assert_eq!(rows[1], "7\tloop\ttab\\there\tfor f in *; do\\n  echo \"$f\"\\ndone\t\tshell,demo\tfalse\t\t\t\tfalse\t\t");
+    }
+}
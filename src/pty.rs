@@ -0,0 +1,215 @@
+use std::env;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+
+use crate::app::BashCmd;
+use crate::config::Config;
+use crate::execute::{resolve_env, resolve_workdir, shell_invocation};
+
+// Embedded terminal: the command runs on a pseudo-terminal whose screen is kept by a vt100
+// parser and drawn in a pane, so full-screen and interactive programs (htop, ssh, psql) work
+// without leaving the TUI. Keys are forwarded except the detach key.
+
+pub struct PtySession {
+    pub name: String,
+    pub parser: Arc<Mutex<vt100::Parser>>,
+    // Exit code and, when the child was killed by one, the signal name.
+    pub exit: Option<(i32, Option<String>)>,
+    size: (u16, u16),
+    master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn Child + Send + Sync>,
+}
+
+fn pty_error(err: impl std::fmt::Display) -> io::Error {
+    io::Error::other(err.to_string())
+}
+
+impl PtySession {
+    pub fn start(command: &BashCmd, config: &Config, rows: u16, cols: u16) -> io::Result<PtySession> {
+        let rows = rows.max(1);
+        let cols = cols.max(1);
+        let pair = native_pty_system()
+            .openpty(PtySize { rows, cols, pixel_width: 0, pixel_height: 0 })
+            .map_err(pty_error)?;
+
+        let (shell, args) = shell_invocation(command, config, true);
+        let mut builder = CommandBuilder::new(shell);
+        builder.args(args);
+        if let Some(cwd) = resolve_workdir(command, config)?.or_else(|| env::current_dir().ok()) {
+            builder.cwd(cwd);
+        }
+        if env::var_os("TERM").is_none() {
+            builder.env("TERM", "xterm-256color");
+        }
+        for (name, value) in resolve_env(command, config)? {
+            builder.env(name, value);
+        }
+        let child = pair.slave.spawn_command(builder).map_err(pty_error)?;
+        // Only the child holds the slave side now, so reads end once it exits.
+        drop(pair.slave);
+
+        let parser = Arc::new(Mutex::new(vt100::Parser::new(rows, cols, 0)));
+        let mut reader = pair.master.try_clone_reader().map_err(pty_error)?;
+        let screen = Arc::clone(&parser);
+        thread::spawn(move || {
+            let mut buffer = [0u8; 8192];
+            while let Ok(read) = reader.read(&mut buffer) {
+                if read == 0 {
+                    break;
+                }
+                if let Ok(mut parser) = screen.lock() {
+                    parser.process(&buffer[..read]);
+                }
+            }
+        });
+        let writer = pair.master.take_writer().map_err(pty_error)?;
+
+        Ok(PtySession {
+            name: command.name.clone(),
+            parser,
+            exit: None,
+            size: (rows, cols),
+            master: pair.master,
+            writer,
+            child,
+        })
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.exit.is_none()
+    }
+
+    // Follows the size of the pane; the child gets SIGWINCH from the pty.
+    pub fn resize(&mut self, rows: u16, cols: u16) {
+        let size = (rows.max(1), cols.max(1));
+        if size == self.size {
+            return;
+        }
+        self.size = size;
+        let _ = self.master.resize(PtySize { rows: size.0, cols: size.1, pixel_width: 0, pixel_height: 0 });
+        if let Ok(mut parser) = self.parser.lock() {
+            parser.set_size(size.0, size.1);
+        }
+    }
+
+    pub fn send_key(&mut self, key: KeyEvent) {
+        let application_cursor = self.parser.lock().map(|parser| parser.screen().application_cursor()).unwrap_or(false);
+        let bytes = key_to_bytes(key, application_cursor);
+        if !bytes.is_empty() {
+            let _ = self.writer.write_all(&bytes);
+            let _ = self.writer.flush();
+        }
+    }
+
+    // Returns true once, when the child has exited.
+    pub fn poll(&mut self) -> bool {
+        if self.exit.is_some() {
+            return false;
+        }
+        match self.child.try_wait() {
+            Ok(Some(status)) => {
+                self.exit = Some((status.exit_code() as i32, status.signal().map(str::to_string)));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn kill(&mut self) {
+        if self.is_running() {
+            let _ = self.child.kill();
+        }
+    }
+}
+
+impl Drop for PtySession {
+    fn drop(&mut self) {
+        self.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// The bytes a terminal sends for a key press. With application cursor mode (used by full-screen
+// programs) the arrow keys send SS3 instead of CSI sequences.
+pub fn key_to_bytes(key: KeyEvent, application_cursor: bool) -> Vec<u8> {
+    let cursor = |code: u8| -> Vec<u8> {
+        if application_cursor { vec![0x1b, b'O', code] } else { vec![0x1b, b'[', code] }
+    };
+    let tilde = |number: &str| format!("\x1b[{}~", number).into_bytes();
+
+    let mut bytes = match key.code {
+        KeyCode::Char(ch) if key.modifiers.contains(KeyModifiers::CONTROL) => match ch.to_ascii_lowercase() {
+            ch @ 'a'..='z' => vec![ch as u8 - b'a' + 1],
+            ' ' | '@' | '2' => vec![0],
+            '[' | '3' => vec![0x1b],
+            '\\' | '4' => vec![0x1c],
+            ']' | '5' => vec![0x1d],
+            '^' | '6' => vec![0x1e],
+            '_' | '7' | '/' => vec![0x1f],
+            _ => ch.to_string().into_bytes(),
+        },
+        KeyCode::Char(ch) => ch.to_string().into_bytes(),
+        KeyCode::Enter => vec![b'\r'],
+        KeyCode::Backspace => vec![0x7f],
+        KeyCode::Tab => vec![b'\t'],
+        KeyCode::BackTab => b"\x1b[Z".to_vec(),
+        KeyCode::Esc => vec![0x1b],
+        KeyCode::Up => cursor(b'A'),
+        KeyCode::Down => cursor(b'B'),
+        KeyCode::Right => cursor(b'C'),
+        KeyCode::Left => cursor(b'D'),
+        KeyCode::Home => cursor(b'H'),
+        KeyCode::End => cursor(b'F'),
+        KeyCode::Insert => tilde("2"),
+        KeyCode::Delete => tilde("3"),
+        KeyCode::PageUp => tilde("5"),
+        KeyCode::PageDown => tilde("6"),
+        KeyCode::F(n @ 1..=4) => vec![0x1b, b'O', b'P' + n - 1],
+        KeyCode::F(n) => match n {
+            5 => tilde("15"),
+            6 => tilde("17"),
+            7 => tilde("18"),
+            8 => tilde("19"),
+            9 => tilde("20"),
+            10 => tilde("21"),
+            11 => tilde("23"),
+            12 => tilde("24"),
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    };
+    if key.modifiers.contains(KeyModifiers::ALT) && !bytes.is_empty() {
+        bytes.insert(0, 0x1b);
+    }
+    bytes
+}
+
+// Ctrl+] leaves the embedded terminal, as in telnet. Terminals report it as Ctrl+5.
+pub fn is_detach_key(key: KeyEvent) -> bool {
+    key.modifiers.contains(KeyModifiers::CONTROL) && matches!(key.code, KeyCode::Char(']') | KeyCode::Char('5'))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn encodes_keys_like_a_terminal() {
+        assert_eq!(key_to_bytes(key(KeyCode::Char('c'), KeyModifiers::CONTROL), false), vec![3]);
+        assert_eq!(key_to_bytes(key(KeyCode::Char('x'), KeyModifiers::ALT), false), b"\x1bx".to_vec());
+        assert_eq!(key_to_bytes(key(KeyCode::Up, KeyModifiers::NONE), false), b"\x1b[A".to_vec());
+        assert_eq!(key_to_bytes(key(KeyCode::Up, KeyModifiers::NONE), true), b"\x1bOA".to_vec());
+        assert_eq!(key_to_bytes(key(KeyCode::F(5), KeyModifiers::NONE), false), b"\x1b[15~".to_vec());
+        assert!(is_detach_key(key(KeyCode::Char('5'), KeyModifiers::CONTROL)));
+    }
+}
@@ -0,0 +1,125 @@
+use crate::app::BashCmd;
+use crate::fuzzy::fuzzy_match;
+
+// Filter box syntax, terms separated by whitespace and all required to match:
+//   text        fuzzy match on name, description, command text or tags
+//   #tag        command carries a tag starting with `tag`
+//   -#tag       command carries no tag starting with `tag`
+//   name:text   fuzzy match on the name only (likewise desc: and cmd:)
+#[derive(Debug, PartialEq)]
+pub enum Term {
+    Text(String),
+    Tag(String),
+    NotTag(String),
+    Name(String),
+    Desc(String),
+    Cmd(String),
+}
+
+const NAME_MATCH_BONUS: i64 = 20;
+
+// A bare `#` or `-#`, as typed on the way to a tag, filters nothing yet.
+pub fn parse_query(query: &str) -> Vec<Term> {
+    query
+        .split_whitespace()
+        .filter(|word| !matches!(*word, "#" | "-#"))
+        .map(|word| {
+            if let Some(tag) = word.strip_prefix("-#") {
+                Term::NotTag(tag.to_lowercase())
+            } else if let Some(tag) = word.strip_prefix('#') {
+                Term::Tag(tag.to_lowercase())
+            } else if let Some(text) = word.strip_prefix("name:") {
+                Term::Name(text.to_string())
+            } else if let Some(text) = word.strip_prefix("desc:") {
+                Term::Desc(text.to_string())
+            } else if let Some(text) = word.strip_prefix("cmd:") {
+                Term::Cmd(text.to_string())
+            } else {
+                Term::Text(word.to_string())
+            }
+        })
+        .collect()
+}
+
+fn has_tag(command: &BashCmd, prefix: &str) -> bool {
+    command.tags.iter().any(|tag| tag.to_lowercase().starts_with(prefix))
+}
+
+fn score_field(term: &str, field: &str) -> Option<i64> {
+    fuzzy_match(term, field).map(|(score, _)| score)
+}
+
+// Returns the summed score and the name characters to highlight, or None if any term fails.
+pub fn score_command(command: &BashCmd, terms: &[Term]) -> Option<(i64, Vec<usize>)> {
+    let mut total = 0;
+    let mut name_positions = Vec::new();
+    for term in terms {
+        total += match term {
+            Term::Tag(prefix) => has_tag(command, prefix).then_some(0)?,
+            Term::NotTag(prefix) => (!has_tag(command, prefix)).then_some(0)?,
+            Term::Desc(text) => score_field(text, &command.desc)?,
+            Term::Cmd(text) => score_field(text, &command.command)?,
+            Term::Name(text) => {
+                let (score, positions) = fuzzy_match(text, &command.name)?;
+                name_positions.extend(positions);
+                score + NAME_MATCH_BONUS
+            }
+            Term::Text(text) => {
+                let mut best: Option<i64> = None;
+                if let Some((score, positions)) = fuzzy_match(text, &command.name) {
+                    best = Some(score + NAME_MATCH_BONUS);
+                    name_positions.extend(positions);
+                }
+                let other_fields = [&command.desc, &command.command].into_iter().chain(command.tags.iter());
+                for field in other_fields {
+                    if let Some(score) = score_field(text, field) {
+                        best = Some(best.map_or(score, |current| current.max(score)));
+                    }
+                }
+                best?
+            }
+        };
+    }
+    name_positions.sort_unstable();
+    name_positions.dedup();
+    Some((total, name_positions))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(name: &str, command: &str, tags: &[&str]) -> BashCmd {
+        BashCmd {
+            name: name.to_string(),
+            command: command.to_string(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parses_operators_and_plain_text() {
+        let terms = parse_query("#Docker -#prod cmd:rsync desc:backup logs");
+        assert_eq!(terms, vec![
+            Term::Tag("docker".to_string()),
+            Term::NotTag("prod".to_string()),
+            Term::Cmd("rsync".to_string()),
+            Term::Desc("backup".to_string()),
+            Term::Text("logs".to_string()),
+        ]);
+        assert_eq!(parse_query("dep # -#"), vec![Term::Text("dep".to_string())]);
+    }
+
+    #[test]
+    fn tag_terms_include_and_exclude() {
+        let staging = command("deploy", "kubectl apply", &["k8s", "staging"]);
+        let prod = command("deploy", "kubectl apply", &["k8s", "prod"]);
+        let terms = parse_query("#k8s -#prod dep");
+
+        assert!(score_command(&staging, &terms).is_some());
+        assert!(score_command(&prod, &terms).is_none());
+        assert!(score_command(&staging, &parse_query("cmd:deploy")).is_none());
+    }
+}
@@ -0,0 +1,291 @@
+use std::io::{self, BufRead, BufReader, Read};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Output, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::app::BashCmd;
+use crate::config::Config;
+use crate::execute::{build_command, exit_code_of};
+
+// "Run inside" mode: the command runs as a child of the TUI with stdout and stderr piped into
+// the output pane, instead of replacing the process via exec.
+
+// Keeps a runaway command from growing the pane without bound; the oldest lines are dropped.
+const MAX_OUTPUT_LINES: usize = 10_000;
+const PIPE_GRACE: Duration = Duration::from_millis(500);
+// A placeholder source that takes longer than this is stopped, so it can't keep the form waiting.
+const SOURCE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct OutputLine {
+    pub text: String,
+    pub is_stderr: bool,
+}
+
+pub struct RunningCommand {
+    pub name: String,
+    pub lines: Vec<OutputLine>,
+    pub started: Instant,
+    // Exit code and duration, set by `poll` once the child has exited and its output is read.
+    pub finished: Option<(i32, Duration)>,
+    child: Child,
+    exited: Option<(i32, Instant)>,
+    receiver: Receiver<OutputLine>,
+    readers_done: bool,
+}
+
+// Drops ANSI escape sequences (colours, cursor movement) and keeps what follows the last carriage
+// return, so progress bars that redraw a line show their final state.
+pub fn clean_line(line: &str) -> String {
+    let line = line.rsplit('\r').find(|part| !part.is_empty()).unwrap_or("");
+    let mut cleaned = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '\u{1b}' {
+            if ch == '\t' {
+                cleaned.push_str("    ");
+            } else if !ch.is_control() {
+                cleaned.push(ch);
+            }
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters up to a final byte in @..~
+            Some('[') => {
+                for next in chars.by_ref() {
+                    if ('@'..='~').contains(&next) {
+                        break;
+                    }
+                }
+            }
+            // OSC: up to BEL or ESC \
+            Some(']') => {
+                while let Some(next) = chars.next() {
+                    if next == '\u{7}' || (next == '\u{1b}' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    cleaned
+}
+
+fn forward_lines<R: Read + Send + 'static>(stream: R, is_stderr: bool, sender: Sender<OutputLine>) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(stream);
+        let mut buffer = Vec::new();
+        // Lines are read as bytes so output that isn't valid UTF-8 doesn't end the stream.
+        while let Ok(read) = reader.read_until(b'\n', &mut buffer) {
+            if read == 0 {
+                break;
+            }
+            let text = clean_line(String::from_utf8_lossy(&buffer).trim_end_matches(['\n', '\r']));
+            if sender.send(OutputLine { text, is_stderr }).is_err() {
+                break;
+            }
+            buffer.clear();
+        }
+    });
+}
+
+impl RunningCommand {
+    pub fn start(command: &BashCmd, config: &Config) -> io::Result<RunningCommand> {
+        // Its own process group, so killing it also stops everything the shell started.
+        let mut child = build_command(command, config, false)?
+            .process_group(0)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let (sender, receiver) = mpsc::channel();
+        if let Some(stdout) = child.stdout.take() {
+            forward_lines(stdout, false, sender.clone());
+        }
+        if let Some(stderr) = child.stderr.take() {
+            forward_lines(stderr, true, sender);
+        }
+
+        Ok(RunningCommand {
+            name: command.name.clone(),
+            lines: Vec::new(),
+            started: Instant::now(),
+            finished: None,
+            child,
+            exited: None,
+            receiver,
+            readers_done: false,
+        })
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.finished.is_none()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.finished.map_or_else(|| self.started.elapsed(), |(_, duration)| duration)
+    }
+
+    fn drain_output(&mut self) {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(line) => self.lines.push(line),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.readers_done = true;
+                    break;
+                }
+            }
+        }
+        if self.lines.len() > MAX_OUTPUT_LINES {
+            self.lines.drain(..self.lines.len() - MAX_OUTPUT_LINES);
+        }
+    }
+
+    // Collects new output and checks for exit. Returns true once, when the command has finished.
+    pub fn poll(&mut self) -> bool {
+        self.drain_output();
+        if self.finished.is_some() {
+            return false;
+        }
+        if self.exited.is_none() {
+            if let Ok(Some(status)) = self.child.try_wait() {
+                self.exited = Some((exit_code_of(status), Instant::now()));
+            }
+        }
+        let Some((code, exited_at)) = self.exited else {
+            return false;
+        };
+        // Background jobs started by the command can hold the pipes open after the shell exits;
+        // give the readers a moment, then report the exit anyway and keep collecting their output.
+        if self.readers_done || exited_at.elapsed() >= PIPE_GRACE {
+            self.finished = Some((code, exited_at - self.started));
+            return true;
+        }
+        false
+    }
+
+    pub fn kill(&mut self) {
+        if self.exited.is_none() {
+            // SAFETY: plain syscall; the group ID is the PID of our own, not yet reaped, child.
+            unsafe {
+                libc::kill(-(self.child.id() as libc::pid_t), libc::SIGKILL);
+            }
+        }
+    }
+}
+
+impl Drop for RunningCommand {
+    fn drop(&mut self) {
+        self.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn output_lines(output: Output) -> io::Result<Vec<String>> {
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr.lines().next().unwrap_or("").trim().to_string();
+        return Err(io::Error::other(format!("{} ({})", message, output.status)));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect())
+}
+
+// The output lines of a `{{name: command}}` source, collected on a background thread while the
+// parameter form stays responsive.
+pub struct SourceLines {
+    pid: u32,
+    started: Instant,
+    receiver: Receiver<io::Result<Vec<String>>>,
+    done: bool,
+}
+
+impl SourceLines {
+    pub fn start(command: &BashCmd, config: &Config) -> io::Result<SourceLines> {
+        let child = build_command(command, config, false)?
+            .process_group(0)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let pid = child.id();
+
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(child.wait_with_output().and_then(output_lines));
+        });
+
+        Ok(SourceLines { pid, started: Instant::now(), receiver, done: false })
+    }
+
+    // Returns the lines, or why there are none, once: when the source has finished or timed out.
+    pub fn poll(&mut self) -> Option<io::Result<Vec<String>>> {
+        if self.done {
+            return None;
+        }
+        let result = match self.receiver.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) if self.started.elapsed() < SOURCE_TIMEOUT => return None,
+            Err(TryRecvError::Empty) => {
+                self.kill();
+                Err(io::Error::new(io::ErrorKind::TimedOut, format!("timed out after {}s", SOURCE_TIMEOUT.as_secs())))
+            }
+            Err(TryRecvError::Disconnected) => Err(io::Error::other("source stopped without a result")),
+        };
+        self.done = true;
+        Some(result)
+    }
+
+    fn kill(&self) {
+        // SAFETY: plain syscall; the group belongs to our child, which the reader thread has not
+        // reaped as long as no result was sent.
+        unsafe {
+            libc::kill(-(self.pid as libc::pid_t), libc::SIGKILL);
+        }
+    }
+}
+
+impl Drop for SourceLines {
+    // Closing the form stops a source that is still loading.
+    fn drop(&mut self) {
+        if !self.done && matches!(self.receiver.try_recv(), Err(TryRecvError::Empty)) {
+            self.kill();
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_escape_sequences_and_carriage_returns() {
+        assert_eq!(clean_line("\u{1b}[1;32mok\u{1b}[0m\tdone"), "ok    done");
+        assert_eq!(clean_line("10%\r50%\r100%"), "100%");
+        assert_eq!(clean_line("\u{1b}]0;title\u{7}prompt"), "prompt");
+    }
+
+    #[test]
+    fn source_lines_arrive_in_the_background() {
+        let command = BashCmd { command: "printf 'main\\n\\n  dev  \\n'".to_string(), shell: Some("sh".to_string()), ..Default::default() };
+        let mut source = SourceLines::start(&command, &Config::default()).unwrap();
+        let lines = loop {
+            if let Some(result) = source.poll() {
+                break result.unwrap();
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(lines, ["main", "dev"]);
+        assert!(source.poll().is_none());
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::app::BashCmd;
+
+// Source for `bash-commander import-history`: reads the bash, zsh and fish history files, merges
+// them and ranks the distinct command lines by how often they were run.
+
+pub struct HistoryCandidate {
+    pub command: String,
+    pub count: usize,
+}
+
+const IMPORTED_NAME_LENGTH: usize = 40;
+const IMPORTED_TAG: &str = "imported";
+
+fn home_path(relative: &str) -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(relative))
+}
+
+fn bash_history_path() -> Option<PathBuf> {
+    home_path(".bash_history")
+}
+
+fn zsh_history_path() -> Option<PathBuf> {
+    match env::var_os("ZDOTDIR") {
+        Some(dir) => Some(PathBuf::from(dir).join(".zsh_history")),
+        None => home_path(".zsh_history"),
+    }
+}
+
+// fish ignores the platform data dir and always follows XDG, even on macOS.
+fn fish_history_path() -> Option<PathBuf> {
+    let data_dir = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => home_path(".local/share")?,
+    };
+    Some(data_dir.join("fish").join("fish_history"))
+}
+
+fn read_if_exists(path: Option<PathBuf>) -> io::Result<Option<Vec<u8>>> {
+    match path {
+        Some(path) if path.exists() => fs::read(path).map(Some),
+        _ => Ok(None),
+    }
+}
+
+fn is_bash_timestamp(line: &str) -> bool {
+    line.strip_prefix('#').is_some_and(|rest| !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit()))
+}
+
+// With HISTTIMEFORMAT set every entry is preceded by a `#<epoch>` line, and that is the only case
+// where an entry can span several lines; without it each line is one entry.
+pub fn parse_bash_history(contents: &str) -> Vec<String> {
+    if !contents.lines().any(is_bash_timestamp) {
+        return contents.lines().map(str::to_string).collect();
+    }
+
+    let mut entries: Vec<String> = Vec::new();
+    let mut current: Option<String> = None;
+    for line in contents.lines() {
+        if is_bash_timestamp(line) {
+            entries.extend(current.take());
+            current = Some(String::new());
+            continue;
+        }
+        match current.as_mut() {
+            Some(entry) if !entry.is_empty() => {
+                entry.push('\n');
+                entry.push_str(line);
+            }
+            Some(entry) => entry.push_str(line),
+            None => entries.push(line.to_string()),
+        }
+    }
+    entries.extend(current);
+    entries
+}
+
+// zsh escapes bytes >= 0x83 as 0x83 followed by the byte xor 32.
+fn unmetafy(bytes: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.iter();
+    while let Some(&byte) = iter.next() {
+        if byte == 0x83 {
+            if let Some(&next) = iter.next() {
+                output.push(next ^ 32);
+            }
+        } else {
+            output.push(byte);
+        }
+    }
+    output
+}
+
+// `: <start>:<duration>;<command>` when EXTENDED_HISTORY is set, the bare command otherwise.
+fn strip_zsh_extended_prefix(line: &str) -> &str {
+    let Some(rest) = line.strip_prefix(": ") else {
+        return line;
+    };
+    match rest.split_once(';') {
+        Some((meta, command)) if meta.chars().all(|c| c.is_ascii_digit() || c == ':') => command,
+        _ => line,
+    }
+}
+
+// Newlines inside an entry are stored as a backslash at the end of the line.
+pub fn parse_zsh_history(contents: &str) -> Vec<String> {
+    let mut entries = Vec::new();
+    let mut pending: Option<String> = None;
+    for line in contents.lines() {
+        let entry = match pending.take() {
+            Some(mut entry) => {
+                entry.push('\n');
+                entry.push_str(line);
+                entry
+            }
+            None => strip_zsh_extended_prefix(line).to_string(),
+        };
+        match entry.strip_suffix('\\') {
+            Some(continued) => pending = Some(continued.to_string()),
+            None => entries.push(entry),
+        }
+    }
+    entries.extend(pending);
+    entries
+}
+
+fn unescape_fish(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            output.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => output.push('\n'),
+            Some('\\') => output.push('\\'),
+            Some(other) => {
+                output.push('\\');
+                output.push(other);
+            }
+            None => output.push('\\'),
+        }
+    }
+    output
+}
+
+// fish_history is YAML-like; only the `- cmd:` lines matter, `when:` and `paths:` are skipped.
+pub fn parse_fish_history(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .filter_map(|line| line.strip_prefix("- cmd: "))
+        .map(unescape_fish)
+        .collect()
+}
+
+// Entries are expected oldest first; ties in frequency go to the most recently run command.
+pub fn rank_by_frequency(entries: Vec<String>) -> Vec<HistoryCandidate> {
+    let mut stats: HashMap<String, (usize, usize)> = HashMap::new();
+    for (position, entry) in entries.into_iter().enumerate() {
+        let command = entry.trim();
+        if command.is_empty() {
+            continue;
+        }
+        let stat = stats.entry(command.to_string()).or_insert((0, 0));
+        stat.0 += 1;
+        stat.1 = position;
+    }
+
+    let mut ranked: Vec<(String, (usize, usize))> = stats.into_iter().collect();
+    ranked.sort_by(|(_, (count_a, last_a)), (_, (count_b, last_b))| count_b.cmp(count_a).then(last_b.cmp(last_a)));
+    ranked
+        .into_iter()
+        .map(|(command, (count, _))| HistoryCandidate { command, count })
+        .collect()
+}
+
+// Every history file found, ranked, minus the commands that are already saved.
+pub fn load_candidates(saved: &[BashCmd]) -> io::Result<Vec<HistoryCandidate>> {
+    let mut entries = Vec::new();
+    if let Some(bytes) = read_if_exists(bash_history_path())? {
+        entries.extend(parse_bash_history(&String::from_utf8_lossy(&bytes)));
+    }
+    if let Some(bytes) = read_if_exists(zsh_history_path())? {
+        entries.extend(parse_zsh_history(&String::from_utf8_lossy(&unmetafy(&bytes))));
+    }
+    if let Some(bytes) = read_if_exists(fish_history_path())? {
+        entries.extend(parse_fish_history(&String::from_utf8_lossy(&bytes)));
+    }
+
+    let mut candidates = rank_by_frequency(entries);
+    candidates.retain(|candidate| !saved.iter().any(|command| command.command.trim() == candidate.command));
+    Ok(candidates)
+}
+
+// Imported commands are named after their first line and tagged so they are easy to review later.
+pub fn imported_command(command_text: &str) -> BashCmd {
+    let first_line = command_text.lines().next().unwrap_or("");
+    let mut name: String = first_line.chars().take(IMPORTED_NAME_LENGTH).collect();
+    if first_line.chars().count() > IMPORTED_NAME_LENGTH || command_text.contains('\n') {
+        name.push('…');
+    }
+
+    BashCmd {
+        name,
+        command: command_text.to_string(),
+        tags: vec![IMPORTED_TAG.to_string()],
+        ..Default::default()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_zsh_extended_and_multiline_entries() {
+        let contents = ": 1700000000:0;git status\n: 1700000005:2;for f in *; do\\\n  echo $f\\\ndone\nls -la\n";
+        assert_eq!(parse_zsh_history(contents), vec!["git status", "for f in *; do\n  echo $f\ndone", "ls -la"]);
+        assert_eq!(unmetafy(&[b'a', 0x83, 0xa3 ^ 32]), vec![b'a', 0xa3]);
+    }
+
+    #[test]
+    fn parses_bash_and_fish_entries() {
+        assert_eq!(parse_bash_history("ls\ncd /tmp\n"), vec!["ls", "cd /tmp"]);
+        assert_eq!(parse_bash_history("#1700000000\nls\n#1700000001\nif true; then\n  echo hi\nfi\n"),
+            vec!["ls", "if true; then\n  echo hi\nfi"]);
+
+        let fish = "- cmd: echo a\\\\b\n  when: 1700000000\n- cmd: printf '1\\n2'\n  when: 1700000001\n  paths:\n    - /tmp\n";
+        assert_eq!(parse_fish_history(fish), vec!["echo a\\b", "printf '1\n2'"]);
+    }
+
+    #[test]
+    fn ranks_by_frequency_then_recency() {
+        let entries = ["ls", "git status", "make", "ls", "  ", "make"].iter().map(|e| e.to_string()).collect();
+        let ranked: Vec<String> = rank_by_frequency(entries).into_iter().map(|candidate| candidate.command).collect();
+        assert_eq!(ranked, vec!["make", "ls", "git status"]);
+    }
+}
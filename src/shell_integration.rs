@@ -0,0 +1,79 @@
+// Snippets printed by `bash-commander init <shell>`. Each binds Ctrl+G to a widget that opens
+// the TUI in emit mode and inserts the chosen command at the cursor instead of running it, and
+// Alt+S to `save-last`, which saves the previously executed command line exactly as typed.
+// Usage: eval "$(bash-commander init bash)" in ~/.bashrc, likewise for zsh;
+// bash-commander init fish | source in config.fish.
+
+const BASH_INIT: &str = r#"__bash_commander_widget() {
+  local selected
+  selected="$(bash-commander emit)"
+  if [ -n "$selected" ]; then
+    READLINE_LINE="${READLINE_LINE:0:$READLINE_POINT}${selected}${READLINE_LINE:$READLINE_POINT}"
+    READLINE_POINT=$(( READLINE_POINT + ${#selected} ))
+  fi
+}
+bind -x '"\C-g": __bash_commander_widget'
+
+__bash_commander_save_last() {
+  local last tty_state
+  # `fc -ln -1` is off by one inside a command substitution, so strip the number from `history 1`.
+  last="$(HISTTIMEFORMAT= builtin history 1)"
+  [[ $last =~ ^\ *[0-9]+\*?\ \ (.*)$ ]] && last="${BASH_REMATCH[1]}"
+  # Readline leaves the terminal in raw mode; the name/description prompts need line input.
+  tty_state="$(stty -g)"
+  stty sane
+  bash-commander save-last "$last"
+  stty "$tty_state"
+}
+bind -x '"\es": __bash_commander_save_last'
+"#;
+
+const ZSH_INIT: &str = r#"__bash_commander_widget() {
+  local selected
+  selected="$(bash-commander emit </dev/tty)"
+  if [[ -n "$selected" ]]; then
+    LBUFFER="${LBUFFER}${selected}"
+  fi
+  zle reset-prompt
+}
+zle -N __bash_commander_widget
+bindkey '^G' __bash_commander_widget
+
+zmodload -i zsh/parameter
+__bash_commander_save_last() {
+  local last="${history[$((HISTCMD - 1))]}"
+  local tty_state="$(stty -g </dev/tty)"
+  zle -I
+  stty sane </dev/tty
+  bash-commander save-last "$last" </dev/tty
+  stty "$tty_state" </dev/tty
+  zle reset-prompt
+}
+zle -N __bash_commander_save_last
+bindkey '^[s' __bash_commander_save_last
+"#;
+
+const FISH_INIT: &str = r#"function __bash_commander_widget
+    set -l selected (bash-commander emit | string collect)
+    if test -n "$selected"
+        commandline --insert -- $selected
+    end
+    commandline -f repaint
+end
+bind \cg __bash_commander_widget
+
+function __bash_commander_save_last
+    bash-commander save-last "$history[1]"
+    commandline -f repaint
+end
+bind \es __bash_commander_save_last
+"#;
+
+pub fn init_script(shell: &str) -> Option<&'static str> {
+    match shell {
+        "bash" => Some(BASH_INIT),
+        "zsh" => Some(ZSH_INIT),
+        "fish" => Some(FISH_INIT),
+        _ => None,
+    }
+}
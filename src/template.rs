@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+
+// Placeholder syntax inside a command:
+//   {{name}}                    plain value
+//   {{name=default}}            value pre-filled with a default
+//   {{name=default#description}} default plus a hint shown in the form
+//   {{name#description}}        hint without a default
+//   {{name: shell command}}     pick the value from the output lines of a command
+// A name used several times is asked for once and substituted everywhere. Names are identifiers
+// (letters, digits, `_` and `-`); anything else between braces, such as the Go templates of
+// `docker --format "{{.State.Status}}"`, is left alone. `\{{` stands for a literal `{{`.
+#[derive(Clone, Debug)]
+pub struct Placeholder {
+    pub name: String,
+    pub default: Option<String>,
+    pub description: Option<String>,
+    pub source: Option<String>,
+}
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+
+// Bare Go template actions like `{{end}}` look like names but belong to the template around them.
+const GO_TEMPLATE_KEYWORDS: [&str; 5] = ["end", "else", "break", "continue", "nil"];
+
+fn is_placeholder_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-')
+        && !GO_TEMPLATE_KEYWORDS.contains(&name)
+}
+
+fn parse_spec(spec: &str) -> Option<Placeholder> {
+    // Everything after the colon is a shell command, which may itself contain `=` or `#`.
+    if let Some((name, source)) = spec.split_once(':') {
+        let name = name.trim();
+        if is_placeholder_name(name) {
+            return Some(Placeholder {
+                name: name.to_string(),
+                default: None,
+                description: None,
+                source: Some(source.trim().to_string()).filter(|source| !source.is_empty()),
+            });
+        }
+    }
+
+    let (head, description) = match spec.split_once('#') {
+        Some((head, description)) => (head, Some(description.trim().to_string())),
+        None => (spec, None),
+    };
+    let (name, default) = match head.split_once('=') {
+        Some((name, default)) => (name, Some(default.trim().to_string())),
+        None => (head, None),
+    };
+    let name = name.trim();
+    if !is_placeholder_name(name) {
+        return None;
+    }
+
+    Some(Placeholder {
+        name: name.to_string(),
+        default,
+        description: description.filter(|description| !description.is_empty()),
+        source: None,
+    })
+}
+
+enum Segment<'a> {
+    Text(&'a str),
+    Placeholder(&'a str, Placeholder),
+}
+
+fn segments(command: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = command;
+    while let Some(start) = rest.find(OPEN) {
+        if rest[..start].ends_with('\\') {
+            segments.push(Segment::Text(&rest[..start - 1]));
+            segments.push(Segment::Text(OPEN));
+            rest = &rest[start + OPEN.len()..];
+            continue;
+        }
+        let after_open = &rest[start + OPEN.len()..];
+        let Some(end) = after_open.find(CLOSE) else {
+            break;
+        };
+        let raw = &rest[start..start + OPEN.len() + end + CLOSE.len()];
+        segments.push(Segment::Text(&rest[..start]));
+        match parse_spec(&after_open[..end]) {
+            Some(placeholder) => segments.push(Segment::Placeholder(raw, placeholder)),
+            None => segments.push(Segment::Text(raw)),
+        }
+        rest = &after_open[end + CLOSE.len()..];
+    }
+    segments.push(Segment::Text(rest));
+    segments
+}
+
+pub fn parse_placeholders(command: &str) -> Vec<Placeholder> {
+    let mut placeholders: Vec<Placeholder> = Vec::new();
+    for segment in segments(command) {
+        let Segment::Placeholder(_, placeholder) = segment else {
+            continue;
+        };
+        match placeholders.iter_mut().find(|existing| existing.name == placeholder.name) {
+            Some(existing) => {
+                // Later occurrences may carry the default or description the first one left out.
+                if existing.default.is_none() {
+                    existing.default = placeholder.default;
+                }
+                if existing.description.is_none() {
+                    existing.description = placeholder.description;
+                }
+                if existing.source.is_none() {
+                    existing.source = placeholder.source;
+                }
+            }
+            None => placeholders.push(placeholder),
+        }
+    }
+    placeholders
+}
+
+pub fn has_placeholders(command: &str) -> bool {
+    !parse_placeholders(command).is_empty()
+}
+
+// Placeholders without a value are left as written so the preview shows what is still missing.
+// Escaped braces come out as a plain `{{`.
+pub fn expand(command: &str, values: &HashMap<String, String>) -> String {
+    segments(command)
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => text,
+            Segment::Placeholder(raw, placeholder) => values
+                .get(&placeholder.name)
+                .map(String::as_str)
+                .unwrap_or(raw),
+        })
+        .collect()
+}
+
+// The text to run for a command without placeholders, which may still contain escaped braces.
+pub fn unescape(command: &str) -> String {
+    expand(command, &HashMap::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn placeholders_carry_defaults_descriptions_and_sources() {
+        let placeholders = parse_placeholders("kubectl -n {{ns=default#Namespace}} logs {{pod: kubectl get pods -o name}} {{flags#extra=args}}");
+        assert_eq!(placeholders.len(), 3);
+        assert_eq!(placeholders[0].name, "ns");
+        assert_eq!(placeholders[0].default.as_deref(), Some("default"));
+        assert_eq!(placeholders[0].description.as_deref(), Some("Namespace"));
+        assert_eq!(placeholders[1].name, "pod");
+        assert_eq!(placeholders[1].source.as_deref(), Some("kubectl get pods -o name"));
+        assert_eq!(placeholders[2].default, None);
+        assert_eq!(placeholders[2].description.as_deref(), Some("extra=args"));
+    }
+
+    #[test]
+    fn repeated_names_are_asked_for_once_and_filled_everywhere() {
+        let command = "cp {{file}} {{file=a.txt}}.bak";
+        let placeholders = parse_placeholders(command);
+        assert_eq!(placeholders.len(), 1);
+        assert_eq!(placeholders[0].default.as_deref(), Some("a.txt"));
+        assert_eq!(expand(command, &values(&[("file", "b.txt")])), "cp b.txt b.txt.bak");
+    }
+
+    #[test]
+    fn unfilled_and_unclosed_placeholders_stay_as_written() {
+        assert_eq!(expand("echo {{who}} {{greeting", &HashMap::new()), "echo {{who}} {{greeting");
+        assert_eq!(parse_placeholders("echo {{greeting").len(), 0);
+    }
+
+    #[test]
+    fn go_templates_pass_through() {
+        for command in [
+            r#"docker inspect --format "{{.State.Status}}" web"#,
+            "kubectl get pods -o go-template='{{range .items}}{{.metadata.name}}{{\"\\n\"}}{{end}}'",
+            r#"docker ps --format "{{printf "%s: %s" .ID .Names}}""#,
+        ] {
+            assert!(!has_placeholders(command), "{command}");
+            assert_eq!(expand(command, &HashMap::new()), command);
+        }
+        let mixed = r#"docker inspect --format "{{.State.Status}}" {{container}}"#;
+        assert_eq!(expand(mixed, &values(&[("container", "web")])), r#"docker inspect --format "{{.State.Status}}" web"#);
+    }
+
+    #[test]
+    fn escaped_braces_are_literal() {
+        let command = r"echo \{{name}} {{name}}";
+        assert_eq!(parse_placeholders(command).len(), 1);
+        assert_eq!(expand(command, &values(&[("name", "x")])), "echo {{name}} x");
+        assert_eq!(unescape(r"echo \{{literal}}"), "echo {{literal}}");
+    }
+}
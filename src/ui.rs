@@ -1,19 +1,24 @@
 use ratatui::{
     Terminal,
-    widgets::{List, ListItem, Block, Borders, Paragraph, BorderType, Clear},
+    buffer::Buffer,
+    widgets::{List, ListItem, Block, Borders, Paragraph, BorderType, Clear, Padding, Widget, Wrap},
     layout::{Layout, Constraint, Direction, Rect, Alignment},
     style::{Style, Color, Modifier},
     text::{Span, Text, Line},
 };
 use crossterm::event::{Event, KeyCode, KeyModifiers};
 use std::io;
+use std::time::Duration;
 
-use crate::app::BashCmd;
+use crate::app::{format_env, BashCmd, RunMode};
+use crate::pty::is_detach_key;
 use crate::commands::save_commands_to_file; 
+use crate::danger;
+use crate::history::{self, Invocation};
 
 
 
-fn draw_command_details(f: &mut ratatui::Frame, command: Option<&BashCmd>, area: Rect) {
+fn draw_command_details(f: &mut ratatui::Frame, command: Option<&BashCmd>, warnings: &[String], area: Rect) {
     f.render_widget(Block::default().borders(Borders::ALL).title("Details").border_type(BorderType::Rounded), area);
 
     let details_layout = Layout::default()
@@ -23,7 +28,6 @@ fn draw_command_details(f: &mut ratatui::Frame, command: Option<&BashCmd>, area:
             Constraint::Length(3), 
             Constraint::Length(3), 
             Constraint::Length(3), 
-            Constraint::Length(3), 
             Constraint::Min(0),    
         ].as_ref())
         .split(area);
@@ -41,7 +45,17 @@ fn draw_command_details(f: &mut ratatui::Frame, command: Option<&BashCmd>, area:
             .block(Block::default().borders(Borders::BOTTOM));
         f.render_widget(command_paragraph, details_layout[2]);
 
-        let category_paragraph = Paragraph::new(Text::from(Line::from(vec![Span::styled("Category: ", Style::default().add_modifier(Modifier::BOLD)), Span::raw(cmd.category.to_string())]))) 
+        let mut category_lines = vec![Line::from(vec![Span::styled("Category: ", Style::default().add_modifier(Modifier::BOLD)), Span::raw(if cmd.category.is_empty() { "-" } else { cmd.category.as_str() }), Span::styled("   Tags: ", Style::default().add_modifier(Modifier::BOLD)), Span::raw(format_tags(&cmd.tags))])];
+        if let Some(workdir) = &cmd.workdir {
+            category_lines.push(Line::from(vec![Span::styled("Directory: ", Style::default().add_modifier(Modifier::BOLD)), Span::raw(workdir.as_str())]));
+        }
+        if !cmd.env.is_empty() {
+            category_lines.push(Line::from(vec![Span::styled("Env: ", Style::default().add_modifier(Modifier::BOLD)), Span::raw(format_env(&cmd.env))]));
+        }
+        // Why running it will ask for confirmation.
+        category_lines.extend(warnings.iter().map(|warning| Line::from(Span::styled(format!("⚠ {}", warning), Style::default().fg(Color::Red)))));
+        let category_paragraph = Paragraph::new(Text::from(category_lines)) 
+            .wrap(Wrap { trim: true })
             .block(Block::default());
         f.render_widget(category_paragraph, details_layout[3]);
 
@@ -59,7 +73,7 @@ fn draw_add_popup(f: &mut ratatui::Frame, app: &crate::app::App) {
     let popup_title = if app.is_editing { "Edit command" } else { "Add command" };
     
     let block = Block::default().title(popup_title).borders(Borders::ALL).border_type(BorderType::Rounded);
-    let popup_area = left_aligned_rect(60, 40, f.size());
+    let popup_area = left_aligned_rect(60, 80, f.area());
     f.render_widget(Clear, popup_area);
     f.render_widget(block, popup_area);
 
@@ -67,6 +81,11 @@ fn draw_add_popup(f: &mut ratatui::Frame, app: &crate::app::App) {
         .direction(Direction::Vertical)
         .margin(2)
         .constraints([
+            Constraint::Length(3), 
+            Constraint::Length(3), 
+            Constraint::Length(3), 
+            Constraint::Length(3), 
+            Constraint::Length(3), 
             Constraint::Length(3), 
             Constraint::Length(3), 
             Constraint::Length(3), 
@@ -98,6 +117,623 @@ fn draw_add_popup(f: &mut ratatui::Frame, app: &crate::app::App) {
     let command_paragraph = Paragraph::new(Text::from(app.popup_input_command.as_str()))
         .block(command_block);
     f.render_widget(command_paragraph, popup_layout[2]);
+
+    
+    let shell_block = Block::default().borders(Borders::ALL).title("Shell (empty = default)")
+        .border_style(if app.popup_input_focused == 3 { focused_style } else { normal_style }); 
+    let shell_paragraph = Paragraph::new(Text::from(app.popup_input_shell.as_str()))
+        .block(shell_block);
+    f.render_widget(shell_paragraph, popup_layout[3]);
+
+    
+    let category_block = Block::default().borders(Borders::ALL).title("Category (Left/Right: pick existing)")
+        .border_style(if app.popup_input_focused == 4 { focused_style } else { normal_style }); 
+    let category_paragraph = Paragraph::new(Text::from(app.popup_input_category.as_str()))
+        .block(category_block);
+    f.render_widget(category_paragraph, popup_layout[4]);
+
+    
+    let tags_block = Block::default().borders(Borders::ALL).title("Tags (comma-separated)")
+        .border_style(if app.popup_input_focused == 5 { focused_style } else { normal_style }); 
+    let tags_paragraph = Paragraph::new(Text::from(app.popup_input_tags.as_str()))
+        .block(tags_block);
+    f.render_widget(tags_paragraph, popup_layout[5]);
+
+    
+    let workdir_block = Block::default().borders(Borders::ALL).title("Working directory (empty = current, ~ and $VARS expanded)")
+        .border_style(if app.popup_input_focused == 6 { focused_style } else { normal_style }); 
+    let workdir_paragraph = Paragraph::new(Text::from(app.popup_input_workdir.as_str()))
+        .block(workdir_block);
+    f.render_widget(workdir_paragraph, popup_layout[6]);
+
+    
+    let env_block = Block::default().borders(Borders::ALL).title("Environment (NAME=value, space-separated)")
+        .border_style(if app.popup_input_focused == 7 { focused_style } else { normal_style }); 
+    let env_paragraph = Paragraph::new(Text::from(app.popup_input_env.as_str()))
+        .block(env_block);
+    f.render_widget(env_paragraph, popup_layout[7]);
+
+    if let Some(error) = &app.popup_error {
+        f.render_widget(Paragraph::new(error.as_str()).style(Style::default().fg(Color::Red)), popup_layout[8]);
+    }
+}
+
+
+fn format_tags(tags: &[String]) -> String {
+    if tags.is_empty() {
+        return "-".to_string();
+    }
+    tags.iter().map(|tag| format!("#{}", tag)).collect::<Vec<String>>().join(" ")
+}
+
+
+fn draw_tags_popup(f: &mut ratatui::Frame, app: &mut crate::app::App) {
+    let block = Block::default().title("Tags").borders(Borders::ALL).border_type(BorderType::Rounded);
+    let popup_area = left_aligned_rect(40, 60, f.area());
+    f.render_widget(Clear, popup_area);
+    f.render_widget(block, popup_area);
+
+    let popup_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ].as_ref())
+        .split(popup_area);
+
+    let entries: Vec<ListItem> = app.tag_counts()
+        .into_iter()
+        .map(|(tag, count)| ListItem::new(Span::styled(format!("#{} ({})", tag, count), Style::default().fg(Color::Gray))))
+        .collect();
+    let list = List::new(entries)
+        .block(Block::default().borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Gray).bg(Color::Blue))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(list, popup_layout[0], &mut app.tags_popup_state);
+
+    let help = "Enter: filter by tag | ESC: close";
+    f.render_widget(Paragraph::new(help).alignment(Alignment::Center), popup_layout[1]);
+}
+
+// Key bindings of the list view, in the order the help popup lists them.
+const KEY_BINDINGS: [(&str, &str); 27] = [
+    ("Type", "Filter (#tag, -#tag, name:, desc:, cmd:)"),
+    ("Up/Down", "Select command"),
+    ("Left/Right", "Switch category"),
+    ("Enter", "Run command (replaces this program)"),
+    ("Ctrl+R", "Run inside, output below the list"),
+    ("Ctrl+P", "Run in terminal / return to it"),
+    ("Alt+D", "Dry run"),
+    ("Ctrl+K", "Kill the running command"),
+    ("PgUp/PgDn", "Scroll output"),
+    ("Ctrl+L", "Close output"),
+    ("Ctrl+A", "Add command"),
+    ("Ctrl+E", "Edit command"),
+    ("Ctrl+D", "Delete command"),
+    ("Ctrl+N", "Duplicate command"),
+    ("Alt+Up/Down", "Move command"),
+    ("Ctrl+F", "Favourite"),
+    ("Alt+F", "Favourites only"),
+    ("Alt+C", "Always confirm"),
+    ("Ctrl+O", "Sort order"),
+    ("Ctrl+G", "Manage categories"),
+    ("Ctrl+T", "Tags"),
+    ("Alt+H", "History"),
+    ("Alt+P", "Environment profile"),
+    ("?", "This help (with an empty filter)"),
+    ("F1", "This help"),
+    ("ESC", "Exit"),
+    ("Ctrl+Q", "Exit"),
+];
+
+fn key_hints(bindings: &[(&str, &str)]) -> Line<'static> {
+    let mut spans = Vec::new();
+    for (i, (key, action)) in bindings.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw(" | "));
+        }
+        spans.push(Span::styled(key.to_string(), Style::default().add_modifier(Modifier::BOLD)));
+        spans.push(Span::raw(format!(": {}", action)));
+    }
+    Line::from(spans)
+}
+
+// The help bar only shows what fits: running the selected command, the keys of the output pane
+// while it is open, and how to see the rest.
+fn help_bar_keys(app: &crate::app::App) -> Vec<(&'static str, &'static str)> {
+    let mut keys = vec![("Enter", "Run"), ("Ctrl+R", "Run inside")];
+    if app.has_live_terminal() {
+        keys.push(("Ctrl+P", "Return to terminal"));
+    } else {
+        keys.push(("Ctrl+P", "Run in terminal"));
+    }
+    if app.is_command_running() {
+        keys.push(("Ctrl+K", "Kill"));
+    }
+    if app.show_output_pane() {
+        keys.extend([("PgUp/PgDn", "Scroll output"), ("Ctrl+L", "Close output")]);
+    } else {
+        keys.extend([("Alt+D", "Dry run"), ("Ctrl+A", "Add"), ("Ctrl+E", "Edit")]);
+    }
+    keys.extend([("?", "All keys"), ("ESC", "Exit")]);
+    keys
+}
+
+fn draw_help_popup(f: &mut ratatui::Frame) {
+    let block = Block::default().title("Keys").borders(Borders::ALL).border_type(BorderType::Rounded);
+    let popup_area = left_aligned_rect(60, 80, f.area());
+    f.render_widget(Clear, popup_area);
+
+    let width = KEY_BINDINGS.iter().map(|(key, _)| key.len()).max().unwrap_or(0) + 2;
+    let mut lines: Vec<Line> = KEY_BINDINGS
+        .iter()
+        .map(|(key, action)| Line::from(vec![
+            Span::styled(format!("{:<width$}", key), Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled(*action, Style::default().fg(Color::Gray)),
+        ]))
+        .collect();
+    lines.push(Line::from(""));
+    lines.push(Line::from("ESC / ?: close"));
+    let help = Paragraph::new(Text::from(lines)).block(block.padding(Padding::horizontal(1)));
+    f.render_widget(help, popup_area);
+}
+
+
+fn draw_profile_popup(f: &mut ratatui::Frame, app: &mut crate::app::App) {
+    let block = Block::default().title("Environment profile").borders(Borders::ALL).border_type(BorderType::Rounded);
+    let popup_area = left_aligned_rect(50, 60, f.area());
+    f.render_widget(Clear, popup_area);
+    f.render_widget(block, popup_area);
+
+    let popup_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ].as_ref())
+        .split(popup_area);
+
+    let active = app.config.profile.as_deref();
+    let mut entries = vec![ListItem::new(Span::styled(
+        format!("{} no profile", if active.is_none() { "●" } else { " " }),
+        Style::default().fg(Color::Gray),
+    ))];
+    for (name, vars) in &app.config.profiles {
+        let marker = if active == Some(name.as_str()) { "●" } else { " " };
+        entries.push(ListItem::new(Line::from(vec![
+            Span::styled(format!("{} {}  ", marker, name), Style::default().fg(Color::Gray)),
+            Span::styled(format_env(vars), Style::default().fg(Color::DarkGray)),
+        ])));
+    }
+    let list = List::new(entries)
+        .block(Block::default().borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Gray).bg(Color::Blue))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(list, popup_layout[0], &mut app.profile_popup_state);
+
+    let help = if app.config.profiles.is_empty() {
+        "No [profiles] in config.toml | ESC: close"
+    } else {
+        "Enter: switch profile | ESC: close"
+    };
+    f.render_widget(Paragraph::new(help).alignment(Alignment::Center), popup_layout[1]);
+}
+
+
+fn draw_history_popup(f: &mut ratatui::Frame, app: &mut crate::app::App) {
+    let block = Block::default().title("History").borders(Borders::ALL).border_type(BorderType::Rounded);
+    let popup_area = left_aligned_rect(80, 70, f.area());
+    f.render_widget(Clear, popup_area);
+    f.render_widget(block, popup_area);
+
+    let popup_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Min(3),
+            Constraint::Length(3),
+            Constraint::Length(1),
+        ].as_ref())
+        .split(popup_area);
+
+    let entries: Vec<ListItem> = app.history_entries
+        .iter()
+        .map(|(number, entry)| {
+            let (status, status_color) = match entry.exit_code {
+                Some(0) => ("0".to_string(), Color::Green),
+                Some(code) => (code.to_string(), Color::Red),
+                None => ("-".to_string(), Color::DarkGray),
+            };
+            let command = if entry.command.is_empty() { &entry.name } else { &entry.command };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{:>5}  {}  ", number, history::format_timestamp(entry.timestamp)), Style::default().fg(Color::DarkGray)),
+                Span::styled(format!("{:>4}  ", status), Style::default().fg(status_color)),
+                Span::styled(format!("{:<8}  ", entry.mode.map_or("", Invocation::label)), Style::default().fg(Color::DarkGray)),
+                Span::styled(command.replace('\n', " ⏎ "), Style::default().fg(Color::Gray)),
+            ]))
+        })
+        .collect();
+    let list = List::new(entries)
+        .block(Block::default().borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Gray).bg(Color::Blue))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(list, popup_layout[0], &mut app.history_popup_state);
+
+    let details = match app.selected_history_entry() {
+        Some((_, entry)) => {
+            let mut first = vec![format!("Command: {}", entry.name)];
+            if let Some(cwd) = &entry.cwd {
+                first.push(format!("Directory: {}", cwd));
+            }
+            if let Some(profile) = &entry.profile {
+                first.push(format!("Profile: {}", profile));
+            }
+            if let Some(finished) = entry.finished {
+                first.push(format!("Took: {}", format_duration(Duration::from_secs(finished.saturating_sub(entry.timestamp)))));
+            }
+            let params: Vec<String> = entry.params.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+            let second = if params.is_empty() { String::new() } else { format!("Params: {}", params.join("  ")) };
+            Text::from(vec![Line::from(first.join("  |  ")), Line::from(second)])
+        }
+        None => Text::from("No runs recorded yet"),
+    };
+    f.render_widget(Paragraph::new(details).style(Style::default().fg(Color::Gray)).wrap(Wrap { trim: false }), popup_layout[1]);
+
+    let help = "Enter: run again | Alt+D: dry run | ESC: close";
+    f.render_widget(Paragraph::new(help).alignment(Alignment::Center), popup_layout[2]);
+}
+
+
+fn draw_category_sidebar(f: &mut ratatui::Frame, app: &mut crate::app::App, area: Rect) {
+    let entries: Vec<ListItem> = app.category_entries()
+        .iter()
+        .map(|entry| {
+            let count = app.items.iter().filter(|item| entry.matches(item)).count();
+            ListItem::new(Span::styled(format!("{} ({})", entry.label(), count), Style::default().fg(Color::Gray)))
+        })
+        .collect();
+
+    let list = List::new(entries)
+        .block(Block::default().title("Categories").borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Gray).bg(Color::Blue));
+    f.render_stateful_widget(list, area, &mut app.category_state);
+}
+
+
+fn draw_category_popup(f: &mut ratatui::Frame, app: &mut crate::app::App) {
+    let block = Block::default().title("Categories").borders(Borders::ALL).border_type(BorderType::Rounded);
+    let popup_area = left_aligned_rect(50, 60, f.area());
+    f.render_widget(Clear, popup_area);
+    f.render_widget(block, popup_area);
+
+    let popup_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Min(3),
+            Constraint::Length(3),
+            Constraint::Length(1),
+        ].as_ref())
+        .split(popup_area);
+
+    let entries: Vec<ListItem> = app.categories
+        .iter()
+        .map(|category| ListItem::new(Span::styled(category.clone(), Style::default().fg(Color::Gray))))
+        .collect();
+    let list = List::new(entries)
+        .block(Block::default().borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Gray).bg(Color::Blue))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(list, popup_layout[0], &mut app.category_popup_state);
+
+    if let Some(input) = &app.category_input {
+        let title = if app.renaming_category.is_some() { "Rename category" } else { "New category" };
+        let input_block = Block::default().borders(Borders::ALL).title(title)
+            .border_style(Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD));
+        f.render_widget(Paragraph::new(Text::from(input.as_str())).block(input_block), popup_layout[1]);
+    }
+
+    let help = if app.category_input.is_some() {
+        "Enter: save | ESC: cancel"
+    } else {
+        "n: new | r: rename | d: delete | ESC: close"
+    };
+    f.render_widget(Paragraph::new(help).alignment(Alignment::Center), popup_layout[2]);
+}
+
+
+fn draw_params_popup(f: &mut ratatui::Frame, app: &mut crate::app::App) {
+    let popup_title = match &app.params_command {
+        Some(command) => format!("Run {}", command.name),
+        None => "Run command".to_string(),
+    };
+
+    let block = Block::default().title(popup_title).borders(Borders::ALL).border_type(BorderType::Rounded);
+    let popup_area = left_aligned_rect(60, 80, f.area());
+    f.render_widget(Clear, popup_area);
+    f.render_widget(block, popup_area);
+
+    let show_options = app.focused_param().is_some_and(|param| param.has_source());
+    let mut constraints: Vec<Constraint> = app.params.iter().map(|_| Constraint::Length(3)).collect();
+    if show_options {
+        constraints.push(Constraint::Min(3));
+        constraints.push(Constraint::Length(4));
+    } else {
+        constraints.push(Constraint::Min(3));
+    }
+    let popup_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints(constraints)
+        .split(popup_area);
+
+    let focused_style = Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD);
+    let normal_style = Style::default();
+
+    for (i, param) in app.params.iter().enumerate() {
+        let placeholder = &param.placeholder;
+        let mut title = match &placeholder.description {
+            Some(description) => format!("{} - {}", placeholder.name, description),
+            None => placeholder.name.clone(),
+        };
+        if param.is_loading() {
+            title = format!("{} (loading…)", title);
+        } else if param.has_source() {
+            title = format!("{} (type to filter, Up/Down to pick)", title);
+        }
+        let param_block = Block::default().borders(Borders::ALL).title(title)
+            .border_style(if app.param_focused == i { focused_style } else { normal_style });
+        let param_paragraph = Paragraph::new(Text::from(param.value.as_str()))
+            .block(param_block);
+        f.render_widget(param_paragraph, popup_layout[i]);
+    }
+
+    if show_options {
+        let options_area = popup_layout[app.params.len()];
+        let param_focused = app.param_focused;
+        let param = &mut app.params[param_focused];
+        let source = param.placeholder.source.as_deref().unwrap_or("");
+        let options_title = match &param.source_error {
+            Some(err) => format!("Options (source failed: {})", err),
+            None if param.is_loading() => format!("Options from `{}` loading… (Esc: cancel)", source),
+            None => format!("Options from `{}`", source),
+        };
+        let options: Vec<ListItem> = param.filtered_options()
+            .into_iter()
+            .map(|option| ListItem::new(Span::styled(option.clone(), Style::default().fg(Color::Gray))))
+            .collect();
+        let options_list = List::new(options)
+            .block(Block::default().title(options_title).borders(Borders::ALL))
+            .highlight_style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Gray).bg(Color::Blue))
+            .highlight_symbol("> ");
+        f.render_stateful_widget(options_list, options_area, &mut param.option_state);
+    }
+
+    let preview_paragraph = Paragraph::new(Text::from(app.expanded_params_command()))
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title("Command (Enter: run, Esc: cancel)"));
+    f.render_widget(preview_paragraph, popup_layout[popup_layout.len() - 1]);
+}
+
+
+fn draw_delete_confirm_popup(f: &mut ratatui::Frame, app: &crate::app::App) {
+    let name = app.deleting_command_id
+        .and_then(|id| app.command_by_id(id))
+        .map(|command| command.name.as_str())
+        .unwrap_or("");
+
+    let block = Block::default().title("Delete command").borders(Borders::ALL).border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(Color::Red));
+    let popup_area = left_aligned_rect(50, 20, f.area());
+    f.render_widget(Clear, popup_area);
+
+    let text = Text::from(vec![
+        Line::from(vec![Span::raw("Delete "), Span::styled(name, Style::default().add_modifier(Modifier::BOLD)), Span::raw("?")]),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("y / Enter", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(": delete | "),
+            Span::styled("n / ESC", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(": cancel"),
+        ]),
+    ]);
+    let paragraph = Paragraph::new(text)
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true })
+        .block(block);
+    f.render_widget(paragraph, popup_area);
+}
+
+
+fn draw_run_confirm_popup(f: &mut ratatui::Frame, app: &crate::app::App) {
+    let Some(command) = app.confirm_command.as_ref() else {
+        return;
+    };
+    let block = Block::default().title("Confirm run").borders(Borders::ALL).border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(Color::Red));
+    let popup_area = left_aligned_rect(60, 50, f.area());
+    f.render_widget(Clear, popup_area);
+    f.render_widget(block, popup_area);
+
+    let popup_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Min(3),
+            Constraint::Length(3),
+            Constraint::Length(1),
+        ].as_ref())
+        .split(popup_area);
+
+    let mut lines = vec![Line::from(Span::styled(command.command.as_str(), Style::default().add_modifier(Modifier::BOLD))), Line::from("")];
+    lines.extend(app.confirm_warnings.iter().map(|warning| Line::from(Span::styled(format!("⚠ {}", warning), Style::default().fg(Color::Red)))));
+    f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), popup_layout[0]);
+
+    let word = danger::confirmation_word(command);
+    let input_style = if app.confirmation_matches() { Style::default().fg(Color::LightGreen) } else { Style::default() };
+    let input = Paragraph::new(app.confirm_input.as_str())
+        .style(input_style)
+        .block(Block::default().borders(Borders::ALL).title(format!("Type '{}' to run it", word)));
+    f.render_widget(input, popup_layout[1]);
+
+    let help = "Enter: run | ESC: cancel";
+    f.render_widget(Paragraph::new(help).alignment(Alignment::Center), popup_layout[2]);
+}
+
+
+fn draw_preview_popup(f: &mut ratatui::Frame, app: &crate::app::App) {
+    let title = app.preview_command.as_ref().map_or("Dry run".to_string(), |command| format!("Dry run: {}", command.name));
+    let block = Block::default().title(title).borders(Borders::ALL).border_type(BorderType::Rounded);
+    let popup_area = left_aligned_rect(70, 60, f.area());
+    f.render_widget(Clear, popup_area);
+    f.render_widget(block, popup_area);
+
+    let popup_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ].as_ref())
+        .split(popup_area);
+
+    let mut lines: Vec<Line> = app.preview_lines.iter().map(|line| Line::from(line.as_str())).collect();
+    if !app.preview_warnings.is_empty() {
+        lines.push(Line::from(""));
+        lines.extend(app.preview_warnings.iter().map(|warning| Line::from(Span::styled(format!("⚠ needs confirmation: {}", warning), Style::default().fg(Color::Red)))));
+    }
+    f.render_widget(Paragraph::new(lines).style(Style::default().fg(Color::Gray)).wrap(Wrap { trim: false }), popup_layout[0]);
+
+    let help = "Enter: run | ESC: close";
+    f.render_widget(Paragraph::new(help).alignment(Alignment::Center), popup_layout[1]);
+}
+
+
+fn vt_color(color: vt100::Color) -> Color {
+    match color {
+        vt100::Color::Default => Color::Reset,
+        vt100::Color::Idx(index) => Color::Indexed(index),
+        vt100::Color::Rgb(r, g, b) => Color::Rgb(r, g, b),
+    }
+}
+
+// Copies the cells of the embedded terminal's screen into the frame buffer.
+struct TerminalScreen<'a> {
+    screen: &'a vt100::Screen,
+}
+
+impl Widget for TerminalScreen<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        for row in 0..area.height {
+            for col in 0..area.width {
+                let Some(cell) = self.screen.cell(row, col) else {
+                    continue;
+                };
+                if cell.is_wide_continuation() {
+                    continue;
+                }
+                let mut style = Style::default().fg(vt_color(cell.fgcolor())).bg(vt_color(cell.bgcolor()));
+                if cell.bold() { style = style.add_modifier(Modifier::BOLD); }
+                if cell.italic() { style = style.add_modifier(Modifier::ITALIC); }
+                if cell.underline() { style = style.add_modifier(Modifier::UNDERLINED); }
+                if cell.inverse() { style = style.add_modifier(Modifier::REVERSED); }
+                let contents = cell.contents();
+                let symbol = if contents.is_empty() { " " } else { contents.as_str() };
+                buf[(area.x + col, area.y + row)].set_symbol(symbol).set_style(style);
+            }
+        }
+    }
+}
+
+fn draw_terminal_pane(f: &mut ratatui::Frame, app: &mut crate::app::App, area: Rect) {
+    let Some(session) = app.terminal_session.as_mut() else {
+        return;
+    };
+    let status = match &session.exit {
+        None => Span::styled("Ctrl+]: back to list", Style::default().fg(Color::Yellow)),
+        Some((code, signal)) => {
+            let reason = match signal {
+                Some(signal) => format!("killed by {}", signal),
+                None => format!("exit {}", code),
+            };
+            let color = if *code == 0 && signal.is_none() { Color::Green } else { Color::Red };
+            Span::styled(format!("{}, press any key to return", reason), Style::default().fg(color))
+        }
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(Line::from(vec![Span::raw(format!("Terminal: {} | ", session.name)), status]));
+    let inner = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    session.resize(inner.height, inner.width);
+    let Ok(parser) = session.parser.lock() else {
+        return;
+    };
+    let screen = parser.screen();
+    f.render_widget(TerminalScreen { screen }, inner);
+    if session.exit.is_none() && !screen.hide_cursor() {
+        let (row, col) = screen.cursor_position();
+        f.set_cursor_position((inner.x + col.min(inner.width.saturating_sub(1)), inner.y + row.min(inner.height.saturating_sub(1))));
+    }
+}
+
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.1}s", duration.as_secs_f64())
+}
+
+fn draw_output_pane(f: &mut ratatui::Frame, app: &crate::app::App, area: Rect) {
+    let Some(running) = &app.running else {
+        let error = app.run_error.as_deref().unwrap_or("");
+        let paragraph = Paragraph::new(Text::from(error))
+            .style(Style::default().fg(Color::Red))
+            .wrap(Wrap { trim: false })
+            .block(Block::default().borders(Borders::ALL).title("Output (failed to start, Ctrl+L: close)"));
+        f.render_widget(paragraph, area);
+        return;
+    };
+
+    let status = match running.finished {
+        None => Span::styled(format!("running {} (Ctrl+K: kill)", format_duration(running.elapsed())), Style::default().fg(Color::Yellow)),
+        Some((0, duration)) => Span::styled(format!("exit 0 in {}", format_duration(duration)), Style::default().fg(Color::Green)),
+        Some((code, duration)) => Span::styled(format!("exit {} in {}", code, format_duration(duration)), Style::default().fg(Color::Red)),
+    };
+    let title = Line::from(vec![Span::raw(format!("Output: {} | ", running.name)), status]);
+
+    // Lines aren't wrapped so the scroll offset counts output lines; the newest are at the bottom.
+    let visible = area.height.saturating_sub(2) as usize;
+    let first = running.lines.len().saturating_sub(visible + app.output_scroll);
+    let lines: Vec<Line> = running.lines[first..]
+        .iter()
+        .take(visible)
+        .map(|line| {
+            let color = if line.is_stderr { Color::Red } else { Color::Gray };
+            Line::from(Span::styled(line.text.as_str(), Style::default().fg(color)))
+        })
+        .collect();
+    let paragraph = Paragraph::new(Text::from(lines))
+        .block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(paragraph, area);
+}
+
+
+fn highlighted_name(name: &str, positions: &[usize]) -> Line<'static> {
+    let normal_style = Style::default().fg(Color::Gray);
+    let match_style = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+    let spans: Vec<Span> = name
+        .chars()
+        .enumerate()
+        .map(|(i, ch)| {
+            let style = if positions.contains(&i) { match_style } else { normal_style };
+            Span::styled(ch.to_string(), style)
+        })
+        .collect();
+    Line::from(spans)
 }
 
 
@@ -114,7 +750,12 @@ fn left_aligned_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
 }
 
 
-pub fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, mut app: crate::app::App) -> io::Result<Option<String>> {
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+// Shorter while the embedded terminal is shown so its screen updates without visible lag.
+const TERMINAL_POLL_INTERVAL: Duration = Duration::from_millis(16);
+const OUTPUT_SCROLL_STEP: usize = 10;
+
+pub fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, app: &mut crate::app::App) -> io::Result<Option<BashCmd>> {
     loop {
         terminal.draw(|f| {
             let main_layout = Layout::default() 
@@ -127,7 +768,15 @@ pub fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, mut app
                         Constraint::Length(2),      
                     ].as_ref()
                 )
-                .split(f.size());
+                .split(f.area());
+
+            let body_layout = Layout::default() 
+                .direction(Direction::Horizontal)
+                .constraints([
+                    Constraint::Length(24), 
+                    Constraint::Min(0), 
+                ].as_ref())
+                .split(main_layout[1]); 
 
             let content_layout = Layout::default() 
                 .direction(Direction::Vertical)
@@ -135,7 +784,9 @@ pub fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, mut app
                     Constraint::Percentage(50), 
                     Constraint::Percentage(50), 
                 ].as_ref())
-                .split(main_layout[1]); 
+                .split(body_layout[1]); 
+
+            draw_category_sidebar(f, app, body_layout[0]);
 
 
             let input_paragraph = Paragraph::new(Text::from(app.filter_text.as_str()))
@@ -152,12 +803,30 @@ pub fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, mut app
             let items: Vec<ListItem> = app.filtered_items
                 .iter()
                 .map(|item| {
-                    ListItem::new(Span::styled(item.name.clone(), Style::default().fg(Color::Gray)))
+                    let positions = app.match_positions.get(&item.id).map(Vec::as_slice).unwrap_or(&[]);
+                    let mut line = highlighted_name(&item.name, positions);
+                    let marker = if item.favourite { "★ " } else { "  " };
+                    line.spans.insert(0, Span::styled(marker, Style::default().fg(Color::Yellow)));
+                    if item.always_confirm {
+                        line.spans.push(Span::styled(" ⚠", Style::default().fg(Color::Red)));
+                    }
+                    ListItem::new(line)
                 })
                 .collect();
 
+            let mut list_title = if app.favourites_only {
+                format!("Commands ({}, favourites only)", app.sort_mode.label())
+            } else {
+                format!("Commands ({})", app.sort_mode.label())
+            };
+            if let Some(profile) = &app.config.profile {
+                list_title.push_str(&format!(" | profile: {}", profile));
+            }
+            if let Some(session) = app.terminal_session.as_ref().filter(|session| session.is_running()) {
+                list_title.push_str(&format!(" | {} running in terminal, Ctrl+P: return", session.name));
+            }
             let list = List::new(items)
-                .block(Block::default().title("Commands").borders(Borders::ALL))
+                .block(Block::default().title(list_title).borders(Borders::ALL))
                 .highlight_style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Gray).bg(Color::Blue))
                 .highlight_symbol("> ");
 
@@ -168,26 +837,70 @@ pub fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, mut app
                 Some(index) => app.filtered_items.get(index),
                 None => None,
             };
-            draw_command_details(f, selected_command, content_layout[1]); 
+            let warnings = selected_command.map(|command| danger::warnings(command, &app.config.danger)).unwrap_or_default();
+            if app.show_output_pane() {
+                let lower_layout = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([
+                        Constraint::Percentage(40),
+                        Constraint::Percentage(60),
+                    ].as_ref())
+                    .split(content_layout[1]);
+                draw_command_details(f, selected_command, &warnings, lower_layout[0]);
+                draw_output_pane(f, app, lower_layout[1]);
+            } else {
+                draw_command_details(f, selected_command, &warnings, content_layout[1]);
+            }
 
 
             
             if app.show_popup {
-                draw_add_popup(f, &app);
+                draw_add_popup(f, app);
             }
 
-                let help_text = Text::from(Line::from(vec![ 
-                Span::styled("ESC / Ctrl+Q", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(": Exit"),
-                Span::raw(" | "), 
-                Span::styled("Ctrl+A", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(": Add command"),
-                Span::raw(" | "), 
-                Span::styled("Ctrl+E", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(": Edit command"),
-                
-            ]));
+            if app.show_params_popup {
+                draw_params_popup(f, app);
+            }
+
+            if app.show_category_popup {
+                draw_category_popup(f, app);
+            }
+
+            if app.show_tags_popup {
+                draw_tags_popup(f, app);
+            }
+
+            if app.show_history_popup {
+                draw_history_popup(f, app);
+            }
+
+            if app.show_profile_popup {
+                draw_profile_popup(f, app);
+            }
+
+            if app.show_help_popup {
+                draw_help_popup(f);
+            }
+
+            if app.preview_command.is_some() {
+                draw_preview_popup(f, app);
+            }
+
+            if app.confirm_command.is_some() {
+                draw_run_confirm_popup(f, app);
+            }
+
+            if app.deleting_command_id.is_some() {
+                draw_delete_confirm_popup(f, app);
+            }
+
+            if app.terminal_attached {
+                draw_terminal_pane(f, app, main_layout[1]);
+            }
+
+            let help_text = Text::from(key_hints(&help_bar_keys(app)));
             let help_paragraph = Paragraph::new(help_text)
+                .wrap(Wrap { trim: true })
                 .style(Style::default().fg(Color::Gray)) 
                 .alignment(Alignment::Center) 
                 .block(Block::default().style(Style::default().bg(Color::DarkGray))); 
@@ -196,27 +909,203 @@ pub fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, mut app
    
         })?;
 
+        // Poll instead of blocking so output of a command running inside keeps streaming in.
+        app.poll_running();
+        app.poll_terminal();
+        app.poll_params();
+        let poll_interval = if app.terminal_attached { TERMINAL_POLL_INTERVAL } else { EVENT_POLL_INTERVAL };
+        if !crossterm::event::poll(poll_interval)? {
+            continue;
+        }
         if let Event::Key(key) = crossterm::event::read()? {
             
-            if !app.show_popup { 
+            if app.terminal_attached {
+                match app.terminal_session.as_mut() {
+                    // Once the command has exited any key returns to the list.
+                    Some(session) if session.is_running() && !is_detach_key(key) => session.send_key(key),
+                    _ => app.detach_terminal(),
+                }
+            } else if app.preview_command.is_some() {
+                match key.code {
+                    KeyCode::Esc => app.hide_preview(),
+                    KeyCode::Enter => {
+                        if let Some(command) = app.run_previewed() {
+                            app.selected_command_to_execute = Some(command);
+                            save_commands_to_file(app)?;
+                            return Ok(app.selected_command_to_execute.clone());
+                        }
+                    }
+                    _ => {}
+                }
+            } else if app.confirm_command.is_some() {
+                match key.code {
+                    KeyCode::Esc => app.cancel_confirm(),
+                    KeyCode::Enter => {
+                        if let Some(command) = app.confirm_run() {
+                            app.selected_command_to_execute = Some(command);
+                            save_commands_to_file(app)?;
+                            return Ok(app.selected_command_to_execute.clone());
+                        }
+                    }
+                    KeyCode::Char(ch) => app.add_char_to_confirm_input(ch),
+                    KeyCode::Backspace => app.remove_char_from_confirm_input(),
+                    _ => {}
+                }
+            } else if app.deleting_command_id.is_some() {
+                match key.code {
+                    KeyCode::Char('y') | KeyCode::Enter => app.confirm_delete(),
+                    KeyCode::Char('n') | KeyCode::Esc => app.cancel_delete(),
+                    _ => {}
+                }
+            } else if app.show_category_popup {
+                if app.category_input.is_some() {
+                    match key.code {
+                        KeyCode::Esc => app.cancel_category_input(),
+                        KeyCode::Enter => app.submit_category_input(),
+                        KeyCode::Char(ch) => app.add_char_to_category_input(ch),
+                        KeyCode::Backspace => app.remove_char_from_category_input(),
+                        _ => {}
+                    }
+                } else {
+                    match key.code {
+                        KeyCode::Esc => app.hide_category_manager(),
+                        KeyCode::Down => app.next_managed_category(),
+                        KeyCode::Up => app.previous_managed_category(),
+                        KeyCode::Char('n') => app.start_new_category(),
+                        KeyCode::Char('r') => app.start_rename_category(),
+                        KeyCode::Char('d') => app.delete_managed_category(),
+                        _ => {}
+                    }
+                }
+            } else if app.show_tags_popup {
+                match key.code {
+                    KeyCode::Esc => app.hide_tags_browser(),
+                    KeyCode::Enter => app.filter_by_selected_tag(),
+                    KeyCode::Down => app.next_tag(),
+                    KeyCode::Up => app.previous_tag(),
+                    _ => {}
+                }
+            } else if app.show_profile_popup {
+                match key.code {
+                    KeyCode::Esc => app.hide_profile_switcher(),
+                    KeyCode::Enter => app.select_highlighted_profile(),
+                    KeyCode::Down => app.next_profile(),
+                    KeyCode::Up => app.previous_profile(),
+                    _ => {}
+                }
+            } else if app.show_history_popup {
+                match key.code {
+                    KeyCode::Esc => app.hide_history_browser(),
+                    KeyCode::Down => app.next_history_entry(),
+                    KeyCode::Up => app.previous_history_entry(),
+                    KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        if let Some(command) = app.replay_selected_history_entry() {
+                            app.request_run(command, RunMode::Preview);
+                        }
+                    }
+                    KeyCode::Enter => {
+                        let replay = app.replay_selected_history_entry();
+                        if let Some(command) = replay.and_then(|command| app.request_run(command, RunMode::Exec)) {
+                            app.selected_command_to_execute = Some(command);
+                            save_commands_to_file(app)?;
+                            return Ok(app.selected_command_to_execute.clone());
+                        }
+                    }
+                    _ => {}
+                }
+            } else if app.show_help_popup {
+                if matches!(key.code, KeyCode::Esc | KeyCode::Enter | KeyCode::Char('?') | KeyCode::F(1)) {
+                    app.show_help_popup = false;
+                }
+            } else if app.show_params_popup {
+                match key.code {
+                    KeyCode::Esc => app.hide_params_form(),
+                    KeyCode::Enter => {
+                        let mode = app.params_run_mode;
+                        let command = app.finish_params_form();
+                        if let Some(command) = command.and_then(|command| app.request_run(command, mode)) {
+                            app.selected_command_to_execute = Some(command);
+                            save_commands_to_file(app)?; 
+                            return Ok(app.selected_command_to_execute.clone()); 
+                        }
+                    }
+                    KeyCode::Tab => app.next_param_focus(),
+                    KeyCode::BackTab => app.previous_param_focus(),
+                    KeyCode::Down => app.param_down(),
+                    KeyCode::Up => app.param_up(),
+                    KeyCode::Char(ch) => app.add_char_to_param_input(ch),
+                    KeyCode::Backspace => app.remove_char_from_param_input(),
+                    _ => {}
+                }
+            } else if !app.show_popup { 
                 match key.code {
                     KeyCode::Char('q') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        save_commands_to_file(&app)?; 
+                        save_commands_to_file(app)?; 
                         return Ok(None)
                     }, 
                     KeyCode::Char('a') if key.modifiers.contains(KeyModifiers::CONTROL) => app.show_add_popup(), 
                     KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) => { 
-                        if let Some(selected_index) = app.state.selected() { 
-                            app.enter_edit_mode(selected_index); 
+                        if let Some(id) = app.selected_command_id() { 
+                            app.enter_edit_mode(id); 
+                        }
+                    },
+                    KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => app.request_delete(),
+                    KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        if let Some(id) = app.selected_command_id() {
+                            app.duplicate_command(id);
                         }
                     },
+                    KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => app.cycle_sort_mode(),
+                    KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => app.show_category_manager(),
+                    KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => app.show_tags_browser(),
+                    // Does nothing while a command is running; the output pane holds one run at a time.
+                    KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        let idle = !app.is_command_running();
+                        if let Some(selected_command) = app.selected_command().cloned().filter(|_| idle) {
+                            app.run_selected(selected_command, RunMode::Inside);
+                        }
+                    },
+                    // Returns to a terminal that is still running rather than starting a second one.
+                    KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) && app.has_live_terminal() => app.attach_terminal(),
+                    KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        if let Some(selected_command) = app.selected_command().cloned() {
+                            app.run_selected(selected_command, RunMode::Terminal);
+                        }
+                    },
+                    KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => app.kill_running(),
+                    KeyCode::Char('l') if key.modifiers.contains(KeyModifiers::CONTROL) => app.close_output_pane(),
+                    KeyCode::PageUp => app.scroll_output(true, OUTPUT_SCROLL_STEP),
+                    KeyCode::PageDown => app.scroll_output(false, OUTPUT_SCROLL_STEP),
+                    KeyCode::Right => app.next_category(),
+                    KeyCode::Left => app.previous_category(),
+                    KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        if let Some(id) = app.selected_command_id() {
+                            app.toggle_favourite(id);
+                        }
+                    },
+                    KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::ALT) => app.toggle_favourites_only(),
+                    KeyCode::Char('h') if key.modifiers.contains(KeyModifiers::ALT) => app.show_history_browser(),
+                    KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::ALT) => app.show_profile_switcher(),
+                    KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        if let Some(selected_command) = app.selected_command().cloned() {
+                            app.run_selected(selected_command, RunMode::Preview);
+                        }
+                    },
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        if let Some(id) = app.selected_command_id() {
+                            app.toggle_always_confirm(id);
+                        }
+                    },
+                    KeyCode::Up if key.modifiers.contains(KeyModifiers::ALT) => app.move_selected(true),
+                    KeyCode::Down if key.modifiers.contains(KeyModifiers::ALT) => app.move_selected(false),
                     KeyCode::Enter => {
-                       if let Some(selected_index) = app.state.selected() {
-                            if let Some(selected_command) = app.filtered_items.get(selected_index) {
-                                
-                                app.selected_command_to_execute = Some(selected_command.command.clone()); 
-                                save_commands_to_file(&app)?; 
-                                return Ok(app.selected_command_to_execute.clone()); 
+                       if let Some(id) = app.selected_command_id() {
+                            if let Some(selected_command) = app.command_by_id(id).cloned() {
+                                if let Some(command) = app.run_selected(selected_command, RunMode::Exec) {
+                                    app.selected_command_to_execute = Some(command); 
+                                    save_commands_to_file(app)?; 
+                                    return Ok(app.selected_command_to_execute.clone()); 
+                                }
                             } else {
                                 app.selected_command_to_execute = None;
                                 return Ok(None); 
@@ -226,6 +1115,9 @@ pub fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, mut app
                             
                         }
                     }
+                    KeyCode::F(1) => app.show_help_popup = true,
+                    // `?` is typed into the filter once there is something there.
+                    KeyCode::Char('?') if app.filter_text.is_empty() => app.show_help_popup = true,
                     KeyCode::Char(ch) => {
                         app.add_char_to_filter(ch);
                     }
@@ -235,7 +1127,7 @@ pub fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, mut app
                     KeyCode::Down => app.next(),
                     KeyCode::Up => app.previous(),
                     KeyCode::Esc => {
-                        save_commands_to_file(&app)?; 
+                        save_commands_to_file(app)?; 
                         return Ok(None)
                     }
                     _ => {}
@@ -245,11 +1137,18 @@ pub fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, mut app
                     KeyCode::Esc => app.hide_add_popup(),
                     KeyCode::Enter => app.save_command(),
                     KeyCode::Tab => app.next_popup_input_focus(), 
+                    KeyCode::Right if app.popup_input_focused == 4 => app.cycle_popup_input_category(true),
+                    KeyCode::Left if app.popup_input_focused == 4 => app.cycle_popup_input_category(false),
                     KeyCode::Char(ch) => { 
                         match app.popup_input_focused {
                             0 => app.add_char_to_popup_input_name(ch), 
                             1 => app.add_char_to_popup_input_desc(ch), 
                             2 => app.add_char_to_popup_input_command(ch), 
+                            3 => app.add_char_to_popup_input_shell(ch), 
+                            4 => app.add_char_to_popup_input_category(ch), 
+                            5 => app.add_char_to_popup_input_tags(ch), 
+                            6 => app.add_char_to_popup_input_workdir(ch), 
+                            7 => app.add_char_to_popup_input_env(ch), 
                             _ => {} 
                         }
                     }
@@ -258,6 +1157,11 @@ pub fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, mut app
                             0 => app.remove_char_from_popup_input_name(), 
                             1 => app.remove_char_from_popup_input_desc(), 
                             2 => app.remove_char_from_popup_input_command(), 
+                            3 => app.remove_char_from_popup_input_shell(), 
+                            4 => app.remove_char_from_popup_input_category(), 
+                            5 => app.remove_char_from_popup_input_tags(), 
+                            6 => app.remove_char_from_popup_input_workdir(), 
+                            7 => app.remove_char_from_popup_input_env(), 
                             _ => {} 
                         }
                     }
@@ -267,3 +1171,84 @@ pub fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, mut app
         }
     }
 }
+
+
+const IMPORT_PAGE_SIZE: isize = 10;
+
+// Multi-select list for `import-history`. Returns the chosen command lines, or nothing on Esc.
+pub fn run_import_picker<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, mut picker: crate::app::ImportPicker) -> io::Result<Vec<String>> {
+    loop {
+        terminal.draw(|f| {
+            let main_layout = Layout::default()
+                .direction(Direction::Vertical)
+                .margin(1)
+                .constraints([
+                    Constraint::Min(0),
+                    Constraint::Length(2),
+                ].as_ref())
+                .split(f.area());
+
+            let items: Vec<ListItem> = picker.candidates
+                .iter()
+                .zip(&picker.chosen)
+                .map(|(candidate, &chosen)| {
+                    let marker = if chosen { "[x] " } else { "[ ] " };
+                    // Multi-line entries are shown on one row; the full text is imported.
+                    let command = candidate.command.replace('\n', " ⏎ ");
+                    ListItem::new(Line::from(vec![
+                        Span::styled(marker, Style::default().fg(Color::Yellow)),
+                        Span::styled(format!("{:>5}  ", candidate.count), Style::default().fg(Color::DarkGray)),
+                        Span::styled(command, Style::default().fg(Color::Gray)),
+                    ]))
+                })
+                .collect();
+
+            let list_title = format!("Import from shell history ({} of {} selected)", picker.chosen_count(), picker.candidates.len());
+            let list = List::new(items)
+                .block(Block::default().title(list_title).borders(Borders::ALL))
+                .highlight_style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Gray).bg(Color::Blue))
+                .highlight_symbol("> ");
+            f.render_stateful_widget(list, main_layout[0], &mut picker.state);
+
+            let help_text = Text::from(Line::from(vec![
+                Span::styled("Space", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(": Select"),
+                Span::raw(" | "),
+                Span::styled("Ctrl+A", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(": Select all / none"),
+                Span::raw(" | "),
+                Span::styled("Enter", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(": Import selected"),
+                Span::raw(" | "),
+                Span::styled("ESC", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(": Cancel"),
+            ]));
+            let help_paragraph = Paragraph::new(help_text)
+                .wrap(Wrap { trim: true })
+                .style(Style::default().fg(Color::Gray))
+                .alignment(Alignment::Center)
+                .block(Block::default().style(Style::default().bg(Color::DarkGray)));
+            f.render_widget(help_paragraph, main_layout[1]);
+        })?;
+
+        if let Event::Key(key) = crossterm::event::read()? {
+            match key.code {
+                KeyCode::Esc => return Ok(Vec::new()),
+                KeyCode::Char('q') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(Vec::new()),
+                KeyCode::Enter => return Ok(picker.chosen_commands()),
+                KeyCode::Char('a') if key.modifiers.contains(KeyModifiers::CONTROL) => picker.toggle_all(),
+                KeyCode::Char(' ') => {
+                    picker.toggle_selected();
+                    picker.move_by(1);
+                }
+                KeyCode::Down => picker.move_by(1),
+                KeyCode::Up => picker.move_by(-1),
+                KeyCode::PageDown => picker.move_by(IMPORT_PAGE_SIZE),
+                KeyCode::PageUp => picker.move_by(-IMPORT_PAGE_SIZE),
+                KeyCode::Home => picker.move_by(isize::MIN),
+                KeyCode::End => picker.move_by(isize::MAX),
+                _ => {}
+            }
+        }
+    }
+}